@@ -22,7 +22,7 @@ fn bench_gcd(b: &mut Bencher) {
         let temp4 = func.insn_call(Some("gcd"), &func, None, &[x - y, y], flags);
         func.insn_return(temp4);
     };
-    let func = UncompiledFunction::compile(func);
+    let func = UncompiledFunction::compile(func).unwrap();
     let func = CompiledFunction::to_closure::<(usize, usize), usize>(func);
     b.iter(|| assert_eq!(func(90, 50), 10));
 }
@@ -143,7 +143,7 @@ fn run(ctx: &mut Context, code: &str) {
     // generate the IR for the code
     generate(&func, code);
     // compile the code and run it
-    let func = UncompiledFunction::compile(func);
+    let func = UncompiledFunction::compile(func).unwrap();
     let mut data: [Cell; 10000] = unsafe { mem::zeroed() };/*
     let closure: &Fn(*mut Cell) = CompiledFunction::to_closure(func);
     closure(data.as_mut_ptr());*/
@@ -6,64 +6,246 @@ extern crate quote;
 use proc_macro::TokenStream;
 
 use quote::Tokens;
-use syn::{Body, Ty, Path};
+use syn::{Attribute, Body, Ident, MetaItem, NestedMetaItem, VariantData};
 
 #[proc_macro_derive(Compile)]
 pub fn hello_world(input: TokenStream) -> TokenStream {
     // Construct a string representation of the type definition
     let s = input.to_string();
-    
+
     // Parse the string representation
     let ast = syn::parse_derive_input(&s).unwrap();
 
     // Build the impl
     let gen = impl_compile(&ast);
-    
+
     // Return the generated impl
     gen.parse().unwrap()
 }
 
-fn jit_type_of(ty: &Ty) -> Tokens {
-    match *ty {
-        Ty::Path(None, ref path @ Path { global: _ , segments: _}) => {
-            quote!(&<#path as jit::Compile>::get_type())
+/// Check if the attributes given contain `#[repr(C)]`
+fn is_repr_c(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| match attr.value {
+        MetaItem::List(ref name, ref items) if name == "repr" => items.iter().any(|item| match *item {
+            NestedMetaItem::MetaItem(MetaItem::Word(ref word)) => word == "C",
+            _ => false
+        }),
+        _ => false
+    })
+}
+
+/// Get the type descriptors of the fields given, as `&Ty`s
+fn jit_types_of(data: &VariantData) -> Vec<Tokens> {
+    data.fields().iter().map(|field| {
+        let ty = &field.ty;
+        quote!(&*<#ty as jit::Compile<'jit>>::get_type())
+    }).collect()
+}
+
+/// Get a pattern that destructures `path` with the data given, and the names
+/// it binds each field to
+fn destructure(path: Tokens, data: &VariantData) -> (Tokens, Vec<Ident>) {
+    let bindings: Vec<Ident> = (0..data.fields().len())
+        .map(|index| Ident::new(format!("field_{}", index)))
+        .collect();
+    let pattern = match *data {
+        VariantData::Struct(ref fields) => {
+            let names = fields.iter().map(|field| field.ident.as_ref().unwrap());
+            let bindings = &bindings;
+            quote!(#path { #(#names: #bindings),* })
         },
-        _ => panic!("type {:?} has no LibJIT equivalent", ty)
-    }
+        VariantData::Tuple(_) => {
+            let bindings = &bindings;
+            quote!(#path ( #(#bindings),* ))
+        },
+        VariantData::Unit => path
+    };
+    (pattern, bindings)
 }
 
 fn impl_compile(ast: &syn::DeriveInput) -> quote::Tokens {
     let name = &ast.ident;
-    match ast.body {
-        Body::Struct(ref variant) => {
-            let mut type_args = Tokens::new();
-            let mut names = Tokens::new();
-            type_args.append("&[");
-            names.append("&[");
-            for field in variant.fields() {
-                type_args.append(jit_type_of(&field.ty));
-                type_args.append(", ");
-                if let Some(ref id) = field.ident {
-                    names.append(format!("{:?}", id.as_ref()));
-                    names.append(", ");
-                }
+    let (_, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let lifetimes = &ast.generics.lifetimes;
+    let ty_params: Vec<Tokens> = ast.generics.ty_params.iter().map(|param| {
+        let ident = &param.ident;
+        let bounds = &param.bounds;
+        quote!(#ident: jit::Compile<'jit> #(+ #bounds)*)
+    }).collect();
+    let impl_generics = quote!(<'jit, #(#lifetimes,)* #(#ty_params),*>);
+    let (compile, get_type) = match ast.body {
+        Body::Struct(ref data) => impl_struct(name, data),
+        Body::Enum(ref variants) => {
+            if !is_repr_c(&ast.attrs) {
+                panic!("#[derive(Compile)] is only defined for #[repr(C)] enums, but {} is not #[repr(C)]", name)
             }
-            type_args.append("]");
-            names.append("]");
+            impl_enum(name, variants)
+        }
+    };
+    let accessors = match ast.body {
+        Body::Struct(VariantData::Struct(ref fields)) => impl_accessors(name, &ast.vis, fields),
+        _ => Tokens::new()
+    };
+    quote!{
+        impl #impl_generics jit::Compile<'jit> for #name #ty_generics #where_clause {
+            fn compile(self, func:&'jit jit::UncompiledFunction) -> &'jit jit::Val {
+                #compile
+            }
+            fn get_type() -> jit::CowType<'jit> {
+                #get_type
+            }
+        }
+        #accessors
+    }
+}
+
+/// Generate the bodies of `compile` and `get_type` for a struct, which is
+/// represented as a LibJIT struct with the same fields
+fn impl_struct(name: &Ident, data: &VariantData) -> (Tokens, Tokens) {
+    let types = jit_types_of(data);
+    let (pattern, bindings) = destructure(quote!(#name), data);
+    let compile = quote!{
+        let ty = <Self as jit::Compile<'jit>>::get_type();
+        let value = jit::Val::new(func, &ty);
+        let #pattern = self;
+        #[allow(unused_mut, unused_variables)]
+        let mut fields = ty.fields();
+        #(func.insn_store_relative(value, fields.next().unwrap().get_offset(), func.insn_of(#bindings));)*
+        value
+    };
+    let get_type = match *data {
+        VariantData::Struct(ref fields) => {
+            let names: Vec<String> = fields.iter()
+                .map(|field| field.ident.as_ref().unwrap().to_string())
+                .collect();
             quote!{
-                impl<'a> Compile<'a> for #name {
-                    fn compile(self, func:&'a jit::UncompiledFunction) -> &'a jit::Val {
-                        jit::Val::new(func, &Self::get_type())
-                    }
-                    fn get_type() -> CowType<'a> {
-                        let mut ty = jit::Type::new_struct(#type_args);
-                        ty.set_names(#names);
-                        ty.into()
-                    }
-                }
+                let mut ty = jit::Type::new_struct(&[#(#types),*]);
+                ty.set_names(&[#(#names),*]);
+                ty.into()
             }
         },
-        _ => panic!("#[derive(Compile)] is only defined for structs, not for enums!")
+        _ => quote!{
+            jit::Type::new_struct(&[#(#types),*]).into()
+        }
+    };
+    (compile, get_type)
+}
 
+/// Generate the bodies of `compile` and `get_type` for a `#[repr(C)]` enum
+///
+/// Enums without any fields are represented as their tag, which is a C `int`.
+/// Otherwise they are represented as a struct with the fields `tag` and `payload`,
+/// where `payload` is a union of a struct for each variant, just like the
+/// layout Rust gives them.
+fn impl_enum(name: &Ident, variants: &[syn::Variant]) -> (Tokens, Tokens) {
+    let mut tags = Vec::new();
+    let mut base = quote!(0);
+    let mut offset = 0i32;
+    for variant in variants {
+        if let Some(ref discriminant) = variant.discriminant {
+            base = quote!(#discriminant);
+            offset = 0;
+        }
+        tags.push(quote!(((#base) as i32 + #offset)));
+        offset += 1;
+    }
+    let tags = &tags;
+    if variants.iter().all(|variant| variant.data.fields().is_empty()) {
+        let patterns: Vec<Tokens> = variants.iter()
+            .map(|variant| {
+                let ident = &variant.ident;
+                destructure(quote!(#name::#ident), &variant.data).0
+            })
+            .collect();
+        let compile = quote!{
+            func.insn_of(match self {
+                #(#patterns => #tags),*
+            })
+        };
+        let get_type = quote!(<i32 as jit::Compile<'jit>>::get_type());
+        return (compile, get_type)
+    }
+    let mut arms = Vec::new();
+    let mut variant_types = Vec::new();
+    for (index, (variant, tag)) in variants.iter().zip(tags).enumerate() {
+        let ident = &variant.ident;
+        let (pattern, bindings) = destructure(quote!(#name::#ident), &variant.data);
+        arms.push(quote!{
+            #pattern => {
+                func.insn_store_relative(value, 0, func.insn_of(#tag));
+                let variant = &variants[#index];
+                #[allow(unused_mut, unused_variables)]
+                let mut fields = variant.get_type().fields();
+                #(func.insn_store_relative(
+                    value,
+                    payload.get_offset() + variant.get_offset() + fields.next().unwrap().get_offset(),
+                    func.insn_of(#bindings)
+                );)*
+            }
+        });
+        let types = jit_types_of(&variant.data);
+        variant_types.push(quote!(jit::Type::new_struct(&[#(#types),*])));
+    }
+    let compile = quote!{
+        let ty = <Self as jit::Compile<'jit>>::get_type();
+        let value = jit::Val::new(func, &ty);
+        let payload = ty.get_field("payload").unwrap();
+        let variants: Vec<_> = payload.get_type().fields().collect();
+        match self {
+            #(#arms)*
+        }
+        value
+    };
+    let get_type = quote!{
+        let variants: Vec<jit::Type> = vec![#(#variant_types),*];
+        let variants: Vec<&jit::Ty> = variants.iter().map(|variant| &**variant).collect();
+        let payload = jit::Type::new_union(&variants);
+        let mut ty = jit::Type::new_struct(&[&*<i32 as jit::Compile<'jit>>::get_type(), &*payload]);
+        ty.set_names(&["tag", "payload"]);
+        ty.into()
+    };
+    (compile, get_type)
+}
+
+/// Generate a trait named after the struct with a getter and a setter for each
+/// of its fields, and implement it for `jit::Val`
+///
+/// These are called `load_<field>` and `store_<field>` so they can never
+/// shadow the inherent methods of `jit::Val`, like `get_type`.
+fn impl_accessors(name: &Ident, vis: &syn::Visibility, fields: &[syn::Field]) -> Tokens {
+    let trait_name = Ident::new(format!("{}Fields", name));
+    let names: Vec<String> = fields.iter()
+        .map(|field| field.ident.as_ref().unwrap().to_string())
+        .collect();
+    let getters: Vec<Ident> = names.iter().map(|name| Ident::new(format!("load_{}", name))).collect();
+    let setters: Vec<Ident> = names.iter().map(|name| Ident::new(format!("store_{}", name))).collect();
+    let get_docs: Vec<String> = names.iter()
+        .map(|field| format!("Load the field `{}` of this `{}`, or of the `{}` this points to", field, name, name))
+        .collect();
+    let set_docs: Vec<String> = names.iter()
+        .map(|field| format!("Store `value` in the field `{}` of the `{}` this points to", field, name))
+        .collect();
+    let (getters, setters, names) = (&getters, &setters, &names);
+    let doc = format!("Accessors for the fields of a `jit::Val` that holds or points to a `{}`", name);
+    quote!{
+        #[doc = #doc]
+        #[allow(dead_code)]
+        #vis trait #trait_name {
+            #(#[doc = #get_docs] fn #getters(&self) -> &jit::Val;)*
+            #(#[doc = #set_docs] fn #setters(&self, value: &jit::Val);)*
+        }
+        impl #trait_name for jit::Val {
+            #(fn #getters(&self) -> &jit::Val {
+                &self[#names]
+            })*
+            #(fn #setters(&self, value: &jit::Val) {
+                let mut ty = self.get_type();
+                while let Some(elem) = ty.get_ref() {
+                    ty = elem;
+                }
+                let field = ty.get_field(#names).unwrap();
+                self.get_function().insn_store_relative(self, field.get_offset(), value);
+            })*
+        }
     }
 }
@@ -0,0 +1,209 @@
+//! Exposing Rust closures as native function pointers
+//!
+//! LibJIT can generate a small piece of code for each closure, called a
+//! trampoline, that unpacks the native arguments it is called with and hands
+//! them to the closure. This lets Rust closures be passed as callbacks to C
+//! libraries that only take a function pointer.
+use raw::*;
+use compile::Compile;
+use context::{Context, SyncContext};
+use types::{get, CowType};
+use std::error::Error;
+use std::marker::PhantomData;
+use std::os::raw::{c_int, c_void};
+use std::ops::Deref;
+use std::panic::{self, AssertUnwindSafe};
+use std::{fmt, mem, ptr};
+
+/// A closure with its native arguments and return value erased
+pub type Erased = Box<Fn(*mut *mut c_void, *mut c_void)>;
+
+/// A function signature that closures can be turned into native callbacks with
+pub trait Signature: Compile<'static> {
+    /// The native function pointer type with this signature
+    type Extern: Copy;
+}
+/// A closure that can be called through a native callback with the signature `S`
+pub trait CallbackFn<S> where S: Signature {
+    /// Wrap this closure so it reads its arguments from LibJIT's argument
+    /// pointers and writes its result into LibJIT's return buffer
+    fn erase(self) -> Erased;
+}
+macro_rules! signature(
+    (fn($($arg:ident),*) -> R) => (
+        impl<$($arg,)* R> Signature for fn($($arg),*) -> R
+            where $($arg: Compile<'static>,)* R: Compile<'static> {
+            type Extern = extern fn($($arg),*) -> R;
+        }
+        impl<$($arg,)* R, F> CallbackFn<fn($($arg),*) -> R> for F
+            where F: Fn($($arg),*) -> R + 'static, $($arg: Compile<'static>,)* R: Compile<'static> {
+            #[allow(non_snake_case, unused_assignments, unused_mut, unused_variables)]
+            fn erase(self) -> Erased {
+                Box::new(move |args, result| unsafe {
+                    let mut index = 0;
+                    $(let $arg = {
+                        index += 1;
+                        ptr::read(*args.offset(index - 1) as *const $arg)
+                    };)*
+                    let value = self($($arg),*);
+                    if mem::size_of::<R>() != 0 {
+                        ptr::write(result as *mut R, value)
+                    }
+                })
+            }
+        }
+    )
+);
+signature!(fn() -> R);
+signature!(fn(A) -> R);
+signature!(fn(A, B) -> R);
+signature!(fn(A, B, C) -> R);
+signature!(fn(A, B, C, D) -> R);
+
+/// A Rust closure that can be called through a native function pointer
+///
+/// The function pointer is only valid for as long as this callback is alive.
+/// The code behind it is allocated in the context the callback was made in,
+/// so it isn't freed until that context is.
+pub struct Callback<'a, S> where S: Signature {
+    _func: Box<Erased>,
+    _signature: CowType<'static>,
+    ptr: *mut c_void,
+    marker: PhantomData<(&'a (), S)>
+}
+impl<'a, S> Callback<'a, S> where S: Signature {
+    /// Get the native function pointer that calls the closure
+    pub fn as_fn(&self) -> S::Extern {
+        unsafe {
+            mem::transmute_copy(&self.ptr)
+        }
+    }
+    /// Get the address of the native function pointer that calls the closure
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.ptr
+    }
+}
+
+/// A callback whose closure can be called from any thread, so its function
+/// pointer can be handed to C code that calls it from other threads
+///
+/// This is made by `make_sync_callback`.
+pub struct SyncCallback<'a, S> where S: Signature {
+    callback: Callback<'a, S>
+}
+unsafe impl<'a, S> Send for SyncCallback<'a, S> where S: Signature {}
+unsafe impl<'a, S> Sync for SyncCallback<'a, S> where S: Signature {}
+impl<'a, S> Deref for SyncCallback<'a, S> where S: Signature {
+    type Target = Callback<'a, S>;
+    fn deref(&self) -> &Callback<'a, S> {
+        &self.callback
+    }
+}
+
+/// The code of the builtin exception a callback throws when its closure
+/// panics, which is caught as `BuiltinException::Unknown(PANIC_CODE)`
+pub const PANIC_CODE: c_int = -20000;
+
+/// The reason a closure couldn't be turned into a callback
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum CallbackError {
+    /// Closures are not supported on this platform
+    Unsupported,
+    /// LibJIT couldn't allocate the trampoline
+    OutOfMemory
+}
+impl fmt::Display for CallbackError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.description())
+    }
+}
+impl fmt::Debug for CallbackError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.description())
+    }
+}
+impl Error for CallbackError {
+    fn description(&self) -> &'static str {
+        match *self {
+            CallbackError::Unsupported => "Closures are not supported on this platform",
+            CallbackError::OutOfMemory => "Failed to allocate the closure's trampoline"
+        }
+    }
+}
+
+extern fn call_closure(_: jit_type_t, result: *mut c_void, args: *mut *mut c_void, data: *mut c_void) {
+    let panicked = unsafe {
+        let func: &Erased = mem::transmute(data);
+        // Unwinding into the C code that called the callback isn't possible,
+        // so a panic is turned into a builtin exception instead
+        panic::catch_unwind(AssertUnwindSafe(|| func(args, result))).is_err()
+    };
+    if panicked {
+        unsafe {
+            jit_exception_builtin(PANIC_CODE);
+        }
+    }
+}
+
+/// Turn `func` into a callback in `context` that C code can call through a
+/// native function pointer with the signature `S`
+///
+/// If the closure panics, the panic is caught and the builtin exception
+/// `PANIC_CODE` is thrown from the callback instead.
+///
+/// ```rust
+/// use jit::Context;
+/// use jit::closure::make_callback;
+/// let ctx = Context::<()>::new();
+/// let offset = 10;
+/// let callback = make_callback::<fn(i32, f64) -> i64, _, _>(&ctx, move |a: i32, b: f64| a as i64 * b as i64 + offset).unwrap();
+/// let func: extern fn(i32, f64) -> i64 = callback.as_fn();
+/// assert_eq!(func(3, 4.0), 22);
+/// ```
+pub fn make_callback<'a, S, F, T>(context: &'a Context<T>, func: F) -> Result<Callback<'a, S>, CallbackError>
+    where S: Signature, F: CallbackFn<S> {
+    if !::supports_closures() {
+        return Err(CallbackError::Unsupported)
+    }
+    unsafe {
+        let func: Box<Erased> = Box::new(func.erase());
+        let signature = get::<S>();
+        let ptr = jit_closure_create(
+            context.into(),
+            (&*signature).into(),
+            Some(call_closure),
+            &*func as *const Erased as *mut c_void
+        );
+        if ptr.is_null() {
+            Err(CallbackError::OutOfMemory)
+        } else {
+            Ok(Callback {
+                _func: func,
+                _signature: signature,
+                ptr: ptr,
+                marker: PhantomData
+            })
+        }
+    }
+}
+
+/// Turn `func` into a callback in `context` that can be called from other
+/// threads
+///
+/// ```rust
+/// use jit::Context;
+/// use jit::closure::make_sync_callback;
+/// use std::thread;
+/// if let Some(ctx) = Context::<()>::new_sync() {
+///     let callback = make_sync_callback::<fn(i32) -> i32, _, _>(&ctx, |x: i32| x * 2).unwrap();
+///     let func: extern fn(i32) -> i32 = callback.as_fn();
+///     let doubled = thread::spawn(move || func(21)).join().unwrap();
+///     assert_eq!(doubled, 42);
+/// }
+/// ```
+pub fn make_sync_callback<'a, S, F, T>(context: &'a SyncContext<T>, func: F) -> Result<SyncCallback<'a, S>, CallbackError>
+    where S: Signature, F: CallbackFn<S> + Send + Sync {
+    make_callback(context, func).map(|callback| SyncCallback {
+        callback: callback
+    })
+}
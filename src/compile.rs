@@ -2,7 +2,7 @@ use function::UncompiledFunction;
 use function::Abi::CDecl;
 use types::get;
 use std::os::raw::c_long;
-use types::{consts, CowType, Type};
+use types::{consts, CowType, Ty, Type};
 use util::from_ptr;
 use value::Val;
 use std::ffi::CStr;
@@ -129,24 +129,50 @@ impl<'a> Compile<'a> for &'a CStr {
     }
 }
 
+/// Get the type of a slice of `elem`, which has the same layout as `&[T]` in Rust
+fn slice_type<'a>(elem: &Ty) -> CowType<'a> {
+    let ty = Type::new_struct(&mut [&*Type::new_pointer(elem), &*get::<usize>()]);
+    unsafe {
+        jit_type_set_size_and_alignment((&ty).into(), mem::size_of::<*mut [u8]>() as i64, mem::align_of::<*mut [u8]>() as i64);
+    }
+    ty.into()
+}
+/// Compile a slice of the type `ty` with the pointer and length given
+fn compile_slice<'a>(func:&'a UncompiledFunction, ty: &Ty, ptr: isize, len: usize) -> &'a Val {
+    let structure = Val::new(func, ty);
+    let mut fields = ty.fields();
+    func.insn_store_relative(structure, fields.next().unwrap().get_offset(), func.insn_of(ptr));
+    func.insn_store_relative(structure, fields.next().unwrap().get_offset(), func.insn_of(len));
+    structure
+}
 impl<'a> Compile<'a> for &'a str {
     #[inline(always)]
     fn compile(self, func:&'a UncompiledFunction) -> &'a Val {
-        unsafe {
-            let ty = Self::get_type();
-            let structure = Val::new(func, &ty);
-            func.insn_store_relative(structure, 0, func.insn_of(mem::transmute::<_, isize>(self.as_ptr())));
-            func.insn_store_relative(structure, mem::size_of::<usize>(), func.insn_of(self.len()));
-            structure
-        }
+        compile_slice(func, &Self::get_type(), self.as_ptr() as isize, self.len())
     }
     #[inline(always)]
     fn get_type() -> CowType<'a> {
-        let ty = Type::new_struct(&mut [&get::<*const u8>(), &get::<usize>()]);
-        unsafe {
-            jit_type_set_size_and_alignment((&ty).into(), mem::size_of::<*mut str>() as i64, mem::align_of::<*mut str>() as i64);
-        }
-        ty.into()
+        slice_type(&get::<u8>())
+    }
+}
+impl<'a, T> Compile<'a> for &'a [T] where T:Compile<'a> + 'a {
+    #[inline(always)]
+    fn compile(self, func:&'a UncompiledFunction) -> &'a Val {
+        compile_slice(func, &Self::get_type(), self.as_ptr() as isize, self.len())
+    }
+    #[inline(always)]
+    fn get_type() -> CowType<'a> {
+        slice_type(&get::<T>())
+    }
+}
+impl<'a, T> Compile<'a> for &'a mut [T] where T:Compile<'a> + 'a {
+    #[inline(always)]
+    fn compile(self, func:&'a UncompiledFunction) -> &'a Val {
+        compile_slice(func, &Self::get_type(), self.as_mut_ptr() as isize, self.len())
+    }
+    #[inline(always)]
+    fn get_type() -> CowType<'a> {
+        slice_type(&get::<T>())
     }
 }
 impl<'a, T> Compile<'a> for (T, ) where T: Compile<'a> {
@@ -159,6 +185,8 @@ impl<'a, T> Compile<'a> for (T, ) where T: Compile<'a> {
         T::get_type()
     }
 }
+compile_arrays!(0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16,
+                17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32);
 compile_tuple!(A, B => a, b);
 compile_tuple!(A, B, C => a, b, c);
 compile_tuple!(A, B, C, D => a, b, c, d);
@@ -1,11 +1,21 @@
 use raw::*;
 use function::Func;
-use util::{oom, from_ptr_opt};
+use util::{ON_DEMAND_DRIVER_META, oom, from_ptr, from_ptr_opt};
+use exception::BuiltinException;
+use memory::{self, MemoryManager};
+use std::os::raw::c_void;
+use std::cell::RefCell;
 use std::default::Default;
 use std::marker::PhantomData;
 use std::{mem, ptr};
-use std::ops::{Index, IndexMut};
+use std::ops::{Deref, Index, IndexMut};
 use cbox::{CBox, DisposeRef};
+
+thread_local!(
+    /// The contexts whose build lock the current thread holds through
+    /// `Context::lock_build`
+    static BUILDING: RefCell<Vec<usize>> = RefCell::new(Vec::new())
+);
 /// Holds all of the functions you have built and compiled. There can be
 /// multiple, but normally there is only one.
 ///
@@ -91,6 +101,80 @@ impl<T> Context<T> {
             CBox::new(jit_context_create())
         }
     }
+    /// Create a new JIT Context whose code size and data allocation are
+    /// controlled by `manager`
+    ///
+    /// The code itself is always kept in LibJIT's default code buffer, so
+    /// `manager` can't put it in an arena of its own.
+    pub fn with_memory_manager<M>(manager: M) -> CBox<Context<T>> where M: MemoryManager {
+        unsafe {
+            let ctx = jit_context_create();
+            if ctx.is_null() {
+                oom()
+            }
+            memory::set_memory_manager(ctx, manager);
+            CBox::new(ctx)
+        }
+    }
+    /// Create a new JIT Context that can be shared between threads, or
+    /// return `None` if LibJIT was built without thread support
+    pub fn new_sync() -> Option<SyncContext<T>> {
+        if ::supports_threads() {
+            Some(SyncContext {
+                context: Context::new()
+            })
+        } else {
+            None
+        }
+    }
+    /// Hold the build lock of this context until the guard returned is dropped
+    ///
+    /// Only one thread can hold the build lock at a time, and it isn't
+    /// re-entrant, so it must not be taken again while it is held. See
+    /// `build` for what takes it.
+    pub fn lock_build(&self) -> Builder<T> {
+        let raw: jit_context_t = self.into();
+        unsafe {
+            jit_context_build_start(raw);
+        }
+        BUILDING.with(|building| building.borrow_mut().push(raw as usize));
+        Builder {
+            context: self
+        }
+    }
+    /// Check if the current thread holds the build lock of this context
+    ///
+    /// This only knows about the lock when it was taken by `lock_build` or
+    /// `build`, not when LibJIT takes it itself.
+    pub fn is_building(&self) -> bool {
+        let raw: jit_context_t = self.into();
+        BUILDING.with(|building| building.borrow().contains(&(raw as usize)))
+    }
+    /// Run `cb` while holding the build lock of this context, which stops
+    /// other threads from building functions in it at the same time
+    ///
+    /// The lock is released when `cb` returns, even if it panics.
+    ///
+    /// The lock isn't re-entrant, so `cb` must not do anything that takes it
+    /// again, or it will deadlock. That includes calling a function made with
+    /// `CompiledFunction::new_on_demand` that hasn't been compiled yet and
+    /// `CompiledFunction::recompile`.
+    ///
+    /// ```rust
+    /// use jit::*;
+    /// let ctx = Context::<()>::new();
+    /// let func = ctx.build(|builder| {
+    ///     let func = UncompiledFunction::new(builder, &get::<fn(i32) -> i32>());
+    ///     func.insn_return(func.insn_neg(&func[0]));
+    ///     UncompiledFunction::compile(func).unwrap()
+    /// });
+    /// let negate: extern fn(i32) -> i32 = func.as_func();
+    /// assert_eq!(negate(5), -5);
+    /// ```
+    pub fn build<R, F>(&self, cb: F) -> R where F: FnOnce(&Builder<T>) -> R {
+        let builder = self.lock_build();
+        cb(&builder)
+    }
     /// Iterate through the functions contained inside this context
     pub fn functions(&self) -> Functions {
         Functions {
@@ -99,7 +183,131 @@ impl<T> Context<T> {
             lifetime: PhantomData,
         }
     }
+    /// Get the function in this context whose closure pointer is given, as made
+    /// by `CompiledFunction::as_func`
+    pub fn function_from_closure(&self, ptr: *mut c_void) -> Option<&Func> {
+        unsafe {
+            from_ptr_opt(jit_function_from_closure(self.into(), ptr))
+        }
+    }
+    /// Get the function in this context whose compiled code contains the
+    /// program counter given
+    ///
+    /// This is useful for working out which function a native stack frame or
+    /// a profiler sample belongs to.
+    pub fn function_from_pc(&self, pc: *mut c_void) -> Option<&Func> {
+        unsafe {
+            from_ptr_opt(jit_function_from_pc(self.into(), pc, ptr::null_mut()))
+        }
+    }
+    /// Get the function in this context whose vtable pointer is given, as made
+    /// by `Func::to_vtable_pointer`
+    pub fn function_from_vtable_pointer(&self, ptr: *mut c_void) -> Option<&Func> {
+        unsafe {
+            from_ptr_opt(jit_function_from_vtable_pointer(self.into(), ptr))
+        }
+    }
+    /// Set the driver that is called instead of LibJIT's own when a function
+    /// made with `CompiledFunction::new_on_demand` is first called
+    ///
+    /// The driver should build and compile the function, normally with
+    /// `Func::compile_on_demand`, then return its entry point. If it returns
+    /// `None`, a compile error is thrown in the caller.
+    pub fn set_on_demand_driver<F>(&self, driver: F) where F: Fn(&Func) -> Option<*mut c_void> + 'static {
+        unsafe {
+            let driver: Box<Box<Fn(&Func) -> Option<*mut c_void>>> = Box::new(Box::new(driver));
+            if jit_context_set_meta(self.into(), ON_DEMAND_DRIVER_META, mem::transmute(driver), Some(::free_data::<Box<Fn(&Func) -> Option<*mut c_void>>>)) == 0 {
+                oom()
+            }
+            jit_context_set_on_demand_driver(self.into(), Some(drive_on_demand));
+        }
+    }
+}
+extern fn drive_on_demand(func: jit_function_t) -> *mut c_void {
+    unsafe {
+        let driver = jit_context_get_meta(jit_function_get_context(func), ON_DEMAND_DRIVER_META);
+        let func: &Func = from_ptr(func);
+        // Without a driver, do what LibJIT's own driver does
+        let entry = if driver.is_null() {
+            func.compile_on_demand()
+        } else {
+            let driver: &Box<Fn(&Func) -> Option<*mut c_void>> = mem::transmute(driver);
+            driver(func)
+        };
+        match entry {
+            Some(entry) => entry,
+            None => {
+                jit_exception_builtin(BuiltinException::CompileError.get_code());
+                ptr::null_mut()
+            }
+        }
+    }
+}
+/// A guard that holds the build lock of a context, which is released when
+/// this is dropped
+pub struct Builder<'a, T> where T: 'a {
+    context: &'a Context<T>
+}
+impl<'a, T> Deref for Builder<'a, T> {
+    type Target = Context<T>;
+    fn deref(&self) -> &Context<T> {
+        self.context
+    }
+}
+impl<'a, T> Drop for Builder<'a, T> {
+    fn drop(&mut self) {
+        let raw: jit_context_t = self.context.into();
+        BUILDING.with(|building| {
+            let mut building = building.borrow_mut();
+            if let Some(index) = building.iter().rposition(|&ctx| ctx == raw as usize) {
+                building.remove(index);
+            }
+        });
+        unsafe {
+            jit_context_build_end(raw);
+        }
+    }
+}
+
+/// A context that can be sent to and shared with other threads, made by
+/// `Context::new_sync` when LibJIT has thread support
+///
+/// LibJIT guards its internal data structures with its own locks when it has
+/// thread support, but functions should still be built while holding the
+/// build lock given by `Context::build`.
+///
+/// ```rust
+/// use jit::*;
+/// use std::sync::Arc;
+/// use std::thread;
+/// if let Some(ctx) = Context::<()>::new_sync() {
+///     let ctx = Arc::new(ctx);
+///     let workers: Vec<_> = (0..4).map(|n| {
+///         let ctx = ctx.clone();
+///         thread::spawn(move || ctx.build(|builder| {
+///             let func = UncompiledFunction::new(builder, &get::<fn() -> i32>());
+///             func.insn_return(func.insn_of(n));
+///             let func = UncompiledFunction::compile(func).unwrap();
+///             let get_n: extern fn() -> i32 = func.as_func();
+///             get_n()
+///         }))
+///     }).collect();
+///     let results: Vec<i32> = workers.into_iter().map(|worker| worker.join().unwrap()).collect();
+///     assert_eq!(results, vec![0, 1, 2, 3]);
+/// }
+/// ```
+pub struct SyncContext<T = ()> {
+    context: CBox<Context<T>>
+}
+unsafe impl<T> Send for SyncContext<T> where T: Send {}
+unsafe impl<T> Sync for SyncContext<T> where T: Send + Sync {}
+impl<T> Deref for SyncContext<T> {
+    type Target = Context<T>;
+    fn deref(&self) -> &Context<T> {
+        &self.context
+    }
 }
+
 impl<T> Drop for Context<T> {
     #[inline(always)]
     fn drop(&mut self) {
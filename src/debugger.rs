@@ -0,0 +1,309 @@
+//! Debugging JIT-compiled code
+//!
+//! Breakpoints are marked in functions as they are built with
+//! `insn_mark_breakpoint`, then a `Debugger` attached to the context decides
+//! which of them actually stop the thread that reaches them.
+use raw::*;
+use context::Context;
+use function::Func;
+use trace::StackTrace;
+use util::{DEBUGGER_HOOK_META, from_ptr, from_ptr_opt, oom};
+use std::os::raw::c_int;
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
+use std::{mem, ptr};
+
+/// The identifier of a thread known to the debugger
+pub type ThreadId = jit_debugger_thread_id_t;
+/// The identifier of a breakpoint added to the debugger
+pub type BreakpointId = jit_debugger_breakpoint_id_t;
+
+/// The `data1` of a breakpoint that marks the start of a source line
+pub const LINE: jit_nint = 10000;
+/// The `data1` of a breakpoint that marks the entry to a function
+pub const ENTER: jit_nint = 10001;
+/// The `data1` of a breakpoint that marks the exit from a function
+pub const LEAVE: jit_nint = 10002;
+/// The `data1` of a breakpoint that marks an exception being thrown
+pub const THROW: jit_nint = 10003;
+
+const TYPE_QUIT: c_int = 0;
+const TYPE_HARD_BREAKPOINT: c_int = 1;
+const TYPE_SOFT_BREAKPOINT: c_int = 2;
+const TYPE_USER_BREAKPOINT: c_int = 3;
+const TYPE_ATTACH_THREAD: c_int = 4;
+const TYPE_DETACH_THREAD: c_int = 5;
+
+const FLAG_THREAD: c_int = 1 << 0;
+const FLAG_FUNCTION: c_int = 1 << 1;
+const FLAG_DATA1: c_int = 1 << 2;
+const FLAG_DATA2: c_int = 1 << 3;
+
+/// Check if debugging is possible on this platform
+#[inline]
+pub fn is_possible() -> bool {
+    unsafe {
+        jit_debugging_possible() != 0
+    }
+}
+
+/// Where a thread stopped because of a breakpoint
+pub struct Stop<'a> {
+    /// The thread that stopped
+    pub thread: ThreadId,
+    /// The function it stopped in
+    pub function: Option<&'a Func>,
+    /// The first value given to `insn_mark_breakpoint`
+    pub data1: jit_nint,
+    /// The second value given to `insn_mark_breakpoint`
+    pub data2: jit_nint,
+    /// The breakpoint that caused the stop, if it was added with `add_breakpoint`
+    pub id: BreakpointId,
+    /// The stack trace of the thread when it stopped
+    ///
+    /// This belongs to LibJIT, so it isn't freed when the stop is dropped.
+    pub trace: Option<ManuallyDrop<StackTrace>>
+}
+
+/// An event reported by `Debugger::wait_event`
+pub enum DebuggerEvent<'a> {
+    /// The debugger is quitting
+    Quit,
+    /// A thread stopped at a breakpoint added with `add_breakpoint`
+    HardBreakpoint(Stop<'a>),
+    /// A thread stopped after `step`, `next` or `finish`
+    SoftBreakpoint(Stop<'a>),
+    /// A thread stopped because of `break_all`
+    UserBreakpoint(Stop<'a>),
+    /// A thread attached itself to the debugger
+    AttachThread(ThreadId),
+    /// A thread detached itself from the debugger
+    DetachThread(ThreadId),
+    /// An event of a type this crate doesn't know about, which has the type
+    /// and thread given
+    Unknown(c_int, ThreadId)
+}
+
+/// Describes which breakpoint markers a breakpoint should stop at
+///
+/// Any criteria left as `None` match every marker.
+#[derive(Clone, Copy, Default)]
+pub struct Breakpoint<'a> {
+    /// Only stop this thread
+    pub thread: Option<ThreadId>,
+    /// Only stop in this function
+    pub function: Option<&'a Func>,
+    /// Only stop at markers with this `data1`
+    pub data1: Option<jit_nint>,
+    /// Only stop at markers with this `data2`
+    pub data2: Option<jit_nint>
+}
+
+/// A debugger attached to a context
+///
+/// Threads running code from the context stop when they reach a breakpoint
+/// marker that matches one of the breakpoints added to the debugger, and
+/// the stop is reported to whichever thread is calling `wait_event`.
+pub struct Debugger<'a> {
+    _debugger: jit_debugger_t,
+    marker: PhantomData<&'a ()>
+}
+impl<'a> Debugger<'a> {
+    /// Create a debugger for the context given
+    pub fn new<T>(ctx: &'a Context<T>) -> Debugger<'a> {
+        unsafe {
+            let debugger = jit_debugger_create(ctx.into());
+            if debugger.is_null() {
+                oom()
+            }
+            Debugger {
+                _debugger: debugger,
+                marker: PhantomData
+            }
+        }
+    }
+    /// Get the context this debugger is attached to
+    pub fn get_context(&self) -> &'a Context {
+        unsafe {
+            jit_debugger_get_context(self._debugger).into()
+        }
+    }
+    /// Get the identifier of the current thread
+    pub fn get_self(&self) -> ThreadId {
+        unsafe {
+            jit_debugger_get_self(self._debugger)
+        }
+    }
+    /// Set whether the current thread can be stopped by breakpoints
+    pub fn set_breakable(&self, breakable: bool) {
+        unsafe {
+            jit_debugger_set_breakable(self._debugger, ptr::null(), breakable as c_int)
+        }
+    }
+    /// Attach the current thread to the debugger, optionally stopping it
+    /// straight away
+    pub fn attach_self(&self, stop_immediately: bool) {
+        unsafe {
+            jit_debugger_attach_self(self._debugger, stop_immediately as c_int)
+        }
+    }
+    /// Detach the current thread from the debugger
+    pub fn detach_self(&self) {
+        unsafe {
+            jit_debugger_detach_self(self._debugger)
+        }
+    }
+    /// Wait for the next event, for up to `timeout` milliseconds or forever
+    /// if it is `None`
+    pub fn wait_event(&self, timeout: Option<u32>) -> Option<DebuggerEvent<'a>> {
+        unsafe {
+            let mut event: jit_debugger_event_t = mem::zeroed();
+            let timeout = timeout.map(|t| t as jit_int).unwrap_or(-1);
+            if jit_debugger_wait_event(self._debugger, &mut event, timeout) == 0 {
+                return None
+            }
+            let stop = || Stop {
+                thread: event.thread,
+                function: from_ptr_opt(event.function),
+                data1: event.data1,
+                data2: event.data2,
+                id: event.id,
+                trace: from_ptr_opt(event.trace).map(ManuallyDrop::new)
+            };
+            Some(match event._type {
+                TYPE_QUIT => DebuggerEvent::Quit,
+                TYPE_HARD_BREAKPOINT => DebuggerEvent::HardBreakpoint(stop()),
+                TYPE_SOFT_BREAKPOINT => DebuggerEvent::SoftBreakpoint(stop()),
+                TYPE_USER_BREAKPOINT => DebuggerEvent::UserBreakpoint(stop()),
+                TYPE_ATTACH_THREAD => DebuggerEvent::AttachThread(event.thread),
+                TYPE_DETACH_THREAD => DebuggerEvent::DetachThread(event.thread),
+                kind => DebuggerEvent::Unknown(kind, event.thread)
+            })
+        }
+    }
+    /// Add a breakpoint, returning its identifier
+    pub fn add_breakpoint(&self, breakpoint: &Breakpoint) -> BreakpointId {
+        unsafe {
+            let mut info: Struct_jit_debugger_breakpoint_info = mem::zeroed();
+            if let Some(thread) = breakpoint.thread {
+                info.flags |= FLAG_THREAD;
+                info.thread = thread;
+            }
+            if let Some(function) = breakpoint.function {
+                info.flags |= FLAG_FUNCTION;
+                info.function = function.into();
+            }
+            if let Some(data1) = breakpoint.data1 {
+                info.flags |= FLAG_DATA1;
+                info.data1 = data1;
+            }
+            if let Some(data2) = breakpoint.data2 {
+                info.flags |= FLAG_DATA2;
+                info.data2 = data2;
+            }
+            jit_debugger_add_breakpoint(self._debugger, &mut info)
+        }
+    }
+    /// Remove the breakpoint with the identifier given
+    pub fn remove_breakpoint(&self, id: BreakpointId) {
+        unsafe {
+            jit_debugger_remove_breakpoint(self._debugger, id)
+        }
+    }
+    /// Remove every breakpoint
+    pub fn remove_all_breakpoints(&self) {
+        unsafe {
+            jit_debugger_remove_all_breakpoints(self._debugger)
+        }
+    }
+    /// Check if the thread given is still alive
+    pub fn is_alive(&self, thread: ThreadId) -> bool {
+        unsafe {
+            jit_debugger_is_alive(self._debugger, thread) != 0
+        }
+    }
+    /// Check if the thread given is running rather than stopped
+    pub fn is_running(&self, thread: ThreadId) -> bool {
+        unsafe {
+            jit_debugger_is_running(self._debugger, thread) != 0
+        }
+    }
+    /// Let a stopped thread run until it reaches another breakpoint
+    pub fn run(&self, thread: ThreadId) {
+        unsafe {
+            jit_debugger_run(self._debugger, thread)
+        }
+    }
+    /// Let a stopped thread run until the next line, stepping into calls
+    pub fn step(&self, thread: ThreadId) {
+        unsafe {
+            jit_debugger_step(self._debugger, thread)
+        }
+    }
+    /// Let a stopped thread run until the next line, stepping over calls
+    pub fn next(&self, thread: ThreadId) {
+        unsafe {
+            jit_debugger_next(self._debugger, thread)
+        }
+    }
+    /// Let a stopped thread run until it returns from the current function
+    pub fn finish(&self, thread: ThreadId) {
+        unsafe {
+            jit_debugger_finish(self._debugger, thread)
+        }
+    }
+    /// Stop every thread at the next breakpoint marker it reaches
+    pub fn break_all(&self) {
+        unsafe {
+            jit_debugger_break(self._debugger)
+        }
+    }
+    /// Make `wait_event` report that the debugger is quitting
+    pub fn quit(&self) {
+        unsafe {
+            jit_debugger_quit(self._debugger)
+        }
+    }
+    /// Call `hook` with the function and data of every breakpoint marker that
+    /// is reached in this debugger's context
+    ///
+    /// While a hook is set, breakpoint markers no longer stop threads for
+    /// the debugger.
+    pub fn set_hook<F>(&self, hook: F) where F: Fn(&Func, jit_nint, jit_nint) + 'static {
+        unsafe {
+            let ctx = jit_debugger_get_context(self._debugger);
+            let hook: Box<Box<Fn(&Func, jit_nint, jit_nint)>> = Box::new(Box::new(hook));
+            if jit_context_set_meta(ctx, DEBUGGER_HOOK_META, mem::transmute(hook), Some(::free_data::<Box<Fn(&Func, jit_nint, jit_nint)>>)) == 0 {
+                oom()
+            }
+            jit_debugger_set_hook(ctx, Some(call_hook));
+        }
+    }
+    /// Remove the hook set with `set_hook`
+    pub fn clear_hook(&self) {
+        unsafe {
+            let ctx = jit_debugger_get_context(self._debugger);
+            jit_debugger_set_hook(ctx, None);
+            jit_context_free_meta(ctx, DEBUGGER_HOOK_META);
+        }
+    }
+}
+impl<'a> Drop for Debugger<'a> {
+    #[inline]
+    fn drop(&mut self) {
+        self.clear_hook();
+        unsafe {
+            jit_debugger_destroy(self._debugger)
+        }
+    }
+}
+
+extern fn call_hook(func: jit_function_t, data1: jit_nint, data2: jit_nint) {
+    unsafe {
+        let hook = jit_context_get_meta(jit_function_get_context(func), DEBUGGER_HOOK_META);
+        if !hook.is_null() {
+            let hook: &Box<Fn(&Func, jit_nint, jit_nint)> = mem::transmute(hook);
+            hook(from_ptr(func), data1, data2)
+        }
+    }
+}
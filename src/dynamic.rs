@@ -0,0 +1,421 @@
+//! Calling functions with arguments whose types are only known at runtime
+use raw::*;
+use exception::{self, JitException};
+use function::Abi;
+use types::{consts, CowType, Ty, Type, TypeKind};
+use std::borrow::Cow;
+use std::error::Error;
+use std::ops::{Deref, DerefMut};
+use std::os::raw::{c_char, c_uint, c_void};
+use std::{cmp, fmt, mem, ptr};
+
+/// The bytes of a struct or union, which start at an address aligned to
+/// the alignment of its type
+pub struct AlignedBytes {
+    buffer: Vec<u8>,
+    offset: usize,
+    len: usize,
+    align: usize
+}
+impl AlignedBytes {
+    /// Make `len` zeroed bytes aligned to `align`, which must be a power of two,
+    /// or zero to not align them
+    pub fn new(len: usize, align: usize) -> AlignedBytes {
+        let align = cmp::max(align, 1);
+        debug_assert!(align.is_power_of_two(), "{} is not a valid alignment", align);
+        let buffer = vec![0; len + align - 1];
+        let offset = (align - buffer.as_ptr() as usize % align) % align;
+        AlignedBytes {
+            buffer: buffer,
+            offset: offset,
+            len: len,
+            align: align
+        }
+    }
+    /// Make zeroed bytes that can hold a value of the type given
+    pub fn zeroed(ty: &Ty) -> AlignedBytes {
+        AlignedBytes::new(ty.get_size(), ty.get_alignment())
+    }
+    /// Get the alignment of these bytes
+    pub fn get_alignment(&self) -> usize {
+        self.align
+    }
+}
+impl Deref for AlignedBytes {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        &self.buffer[self.offset..self.offset + self.len]
+    }
+}
+impl DerefMut for AlignedBytes {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer[self.offset..self.offset + self.len]
+    }
+}
+impl Clone for AlignedBytes {
+    /// Copy these bytes into a new buffer, which is aligned separately
+    fn clone(&self) -> AlignedBytes {
+        let mut bytes = AlignedBytes::new(self.len, self.align);
+        bytes.copy_from_slice(self);
+        bytes
+    }
+}
+impl PartialEq for AlignedBytes {
+    fn eq(&self, other: &AlignedBytes) -> bool {
+        **self == **other
+    }
+}
+impl fmt::Debug for AlignedBytes {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, fmt)
+    }
+}
+
+/// A value of any type LibJIT can pass to or return from a function
+#[derive(Clone, Debug, PartialEq)]
+pub enum DynValue {
+    /// The lack of a value, which is returned by functions returning `void`
+    Void,
+    /// A signed 8-bit integer
+    SByte(i8),
+    /// An unsigned 8-bit integer
+    UByte(u8),
+    /// A signed 16-bit integer
+    Short(i16),
+    /// An unsigned 16-bit integer
+    UShort(u16),
+    /// A signed 32-bit integer
+    Int(i32),
+    /// An unsigned 32-bit integer
+    UInt(u32),
+    /// A signed pointer-sized integer
+    NInt(isize),
+    /// An unsigned pointer-sized integer
+    NUInt(usize),
+    /// A signed 64-bit integer
+    Long(i64),
+    /// An unsigned 64-bit integer
+    ULong(u64),
+    /// A 32-bit float
+    Float32(f32),
+    /// A 64-bit float
+    Float64(f64),
+    /// The native float type
+    NFloat(jit_nfloat),
+    /// A struct of the type given, laid out in the bytes given
+    Struct(Type, AlignedBytes),
+    /// A union of the type given, laid out in the bytes given
+    Union(Type, AlignedBytes),
+    /// A function pointer
+    Signature(*mut c_void),
+    /// A pointer
+    Pointer(*mut c_void),
+    /// A C `bool`
+    SysBool(bool),
+    /// A C `char`
+    SysChar(c_char)
+}
+impl DynValue {
+    /// Make the zero value of the type given, or `None` if the type is tagged
+    /// with something other than a system type
+    pub fn zeroed(ty: &Ty) -> Option<DynValue> {
+        let kind = ty.get_kind();
+        Some(match kind {
+            TypeKind::Void => DynValue::Void,
+            TypeKind::SByte => DynValue::SByte(0),
+            TypeKind::UByte => DynValue::UByte(0),
+            TypeKind::Short => DynValue::Short(0),
+            TypeKind::UShort => DynValue::UShort(0),
+            TypeKind::Int => DynValue::Int(0),
+            TypeKind::UInt => DynValue::UInt(0),
+            TypeKind::NInt => DynValue::NInt(0),
+            TypeKind::NUInt => DynValue::NUInt(0),
+            TypeKind::Long => DynValue::Long(0),
+            TypeKind::ULong => DynValue::ULong(0),
+            TypeKind::Float32 => DynValue::Float32(0.),
+            TypeKind::Float64 => DynValue::Float64(0.),
+            TypeKind::NFloat => DynValue::NFloat(0.),
+            TypeKind::Struct => DynValue::Struct(ty.to_owned(), AlignedBytes::zeroed(ty)),
+            TypeKind::Union => DynValue::Union(ty.to_owned(), AlignedBytes::zeroed(ty)),
+            TypeKind::Signature => DynValue::Signature(ptr::null_mut()),
+            TypeKind::Pointer => DynValue::Pointer(ptr::null_mut()),
+            TypeKind::SysBool => DynValue::SysBool(false),
+            TypeKind::SysChar => DynValue::SysChar(0),
+            _ => return None
+        })
+    }
+    /// Get the type of this value
+    pub fn get_type(&self) -> CowType {
+        match *self {
+            DynValue::Void => consts::get_void().into(),
+            DynValue::SByte(_) => consts::get_sbyte().into(),
+            DynValue::UByte(_) => consts::get_ubyte().into(),
+            DynValue::Short(_) => consts::get_short().into(),
+            DynValue::UShort(_) => consts::get_ushort().into(),
+            DynValue::Int(_) => consts::get_int().into(),
+            DynValue::UInt(_) => consts::get_uint().into(),
+            DynValue::NInt(_) => consts::get_nint().into(),
+            DynValue::NUInt(_) => consts::get_nuint().into(),
+            DynValue::Long(_) => consts::get_long().into(),
+            DynValue::ULong(_) => consts::get_ulong().into(),
+            DynValue::Float32(_) => consts::get_float32().into(),
+            DynValue::Float64(_) => consts::get_float64().into(),
+            DynValue::NFloat(_) => consts::get_nfloat().into(),
+            DynValue::Struct(ref ty, _) | DynValue::Union(ref ty, _) => Cow::Borrowed(&**ty),
+            DynValue::Signature(_) | DynValue::Pointer(_) => consts::get_void_ptr().into(),
+            DynValue::SysBool(_) => consts::get_sys_bool().into(),
+            DynValue::SysChar(_) => consts::get_sys_char().into()
+        }
+    }
+    /// Check if this value can be passed as a parameter of the type given
+    pub fn matches(&self, ty: &Ty) -> bool {
+        match *self {
+            DynValue::Struct(ref own, ref bytes) | DynValue::Union(ref own, ref bytes) =>
+                own.get_kind() == ty.get_kind() && bytes.len() == ty.get_size()
+                    && bytes.get_alignment() >= ty.get_alignment() && **own == *ty,
+            DynValue::Signature(_) | DynValue::Pointer(_) => ty.is_pointer() || ty.is_signature(),
+            // Pointers normalize to integers, so they have to be ruled out first
+            _ => !ty.is_pointer() && !ty.is_signature()
+                && normalized_kind(&self.get_type()) == normalized_kind(ty)
+        }
+    }
+    /// Get a pointer to the contents of this value
+    pub fn as_ptr(&self) -> *mut c_void {
+        match *self {
+            DynValue::Void => ptr::null_mut(),
+            DynValue::SByte(ref v) => v as *const _ as *mut c_void,
+            DynValue::UByte(ref v) => v as *const _ as *mut c_void,
+            DynValue::Short(ref v) => v as *const _ as *mut c_void,
+            DynValue::UShort(ref v) => v as *const _ as *mut c_void,
+            DynValue::Int(ref v) => v as *const _ as *mut c_void,
+            DynValue::UInt(ref v) => v as *const _ as *mut c_void,
+            DynValue::NInt(ref v) => v as *const _ as *mut c_void,
+            DynValue::NUInt(ref v) => v as *const _ as *mut c_void,
+            DynValue::Long(ref v) => v as *const _ as *mut c_void,
+            DynValue::ULong(ref v) => v as *const _ as *mut c_void,
+            DynValue::Float32(ref v) => v as *const _ as *mut c_void,
+            DynValue::Float64(ref v) => v as *const _ as *mut c_void,
+            DynValue::NFloat(ref v) => v as *const _ as *mut c_void,
+            DynValue::Struct(_, ref bytes) | DynValue::Union(_, ref bytes) => bytes.as_ptr() as *mut c_void,
+            DynValue::Signature(ref v) | DynValue::Pointer(ref v) => v as *const _ as *mut c_void,
+            DynValue::SysBool(ref v) => v as *const _ as *mut c_void,
+            DynValue::SysChar(ref v) => v as *const _ as *mut c_void
+        }
+    }
+    /// Get a mutable pointer to the contents of this value
+    pub fn as_mut_ptr(&mut self) -> *mut c_void {
+        match *self {
+            DynValue::Void => ptr::null_mut(),
+            DynValue::SByte(ref mut v) => v as *mut _ as *mut c_void,
+            DynValue::UByte(ref mut v) => v as *mut _ as *mut c_void,
+            DynValue::Short(ref mut v) => v as *mut _ as *mut c_void,
+            DynValue::UShort(ref mut v) => v as *mut _ as *mut c_void,
+            DynValue::Int(ref mut v) => v as *mut _ as *mut c_void,
+            DynValue::UInt(ref mut v) => v as *mut _ as *mut c_void,
+            DynValue::NInt(ref mut v) => v as *mut _ as *mut c_void,
+            DynValue::NUInt(ref mut v) => v as *mut _ as *mut c_void,
+            DynValue::Long(ref mut v) => v as *mut _ as *mut c_void,
+            DynValue::ULong(ref mut v) => v as *mut _ as *mut c_void,
+            DynValue::Float32(ref mut v) => v as *mut _ as *mut c_void,
+            DynValue::Float64(ref mut v) => v as *mut _ as *mut c_void,
+            DynValue::NFloat(ref mut v) => v as *mut _ as *mut c_void,
+            DynValue::Struct(_, ref mut bytes) | DynValue::Union(_, ref mut bytes) => bytes.as_mut_ptr() as *mut c_void,
+            DynValue::Signature(ref mut v) | DynValue::Pointer(ref mut v) => v as *mut _ as *mut c_void,
+            DynValue::SysBool(ref mut v) => v as *mut _ as *mut c_void,
+            DynValue::SysChar(ref mut v) => v as *mut _ as *mut c_void
+        }
+    }
+}
+macro_rules! dyn_from(
+    ($($ty:ty => $variant:ident),+) => ($(
+        impl From<$ty> for DynValue {
+            fn from(value: $ty) -> DynValue {
+                DynValue::$variant(value)
+            }
+        }
+    )+)
+);
+dyn_from!{
+    i8 => SByte, u8 => UByte, i16 => Short, u16 => UShort,
+    i32 => Int, u32 => UInt, isize => NInt, usize => NUInt,
+    i64 => Long, u64 => ULong, f32 => Float32, f64 => Float64,
+    bool => SysBool, *mut c_void => Pointer
+}
+impl From<()> for DynValue {
+    fn from(_: ()) -> DynValue {
+        DynValue::Void
+    }
+}
+
+/// Get the kind of the type given in its basic numeric form
+fn normalized_kind(ty: &Ty) -> TypeKind {
+    unsafe {
+        mem::transmute(jit_type_get_kind(jit_type_normalize(ty.into())))
+    }
+}
+
+/// The reason a function couldn't be applied to some arguments
+#[derive(Clone, PartialEq)]
+pub enum ApplyError {
+    /// The wrong number of arguments was given
+    ArgCount {
+        /// The number of parameters the signature has
+        expected: usize,
+        /// The number of arguments given
+        got: usize
+    },
+    /// An argument didn't match the type of its parameter
+    ArgType {
+        /// The index of the argument
+        index: usize,
+        /// The type of the parameter
+        expected: Type,
+        /// The type of the argument given
+        got: Type
+    },
+    /// The function applied with variable arguments doesn't take them
+    NotVariadic,
+    /// The signature has no return type
+    NoReturn,
+    /// The return type can't be represented as a `DynValue`
+    UnsupportedReturn(Type),
+    /// An exception was thrown by the function
+    Exception(JitException)
+}
+impl fmt::Display for ApplyError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ApplyError::ArgCount { expected, got } =>
+                write!(fmt, "Expected {} arguments, but got {}", expected, got),
+            ApplyError::ArgType { index, ref expected, ref got } =>
+                write!(fmt, "Argument #{} should be {:?}, but got {:?}", index, expected, got),
+            ApplyError::NotVariadic | ApplyError::NoReturn => write!(fmt, "{}", self.description()),
+            ApplyError::UnsupportedReturn(ref ty) =>
+                write!(fmt, "Cannot return a value of type {:?}", ty),
+            ApplyError::Exception(ref err) => write!(fmt, "{}", err)
+        }
+    }
+}
+impl fmt::Debug for ApplyError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, fmt)
+    }
+}
+impl Error for ApplyError {
+    fn description(&self) -> &str {
+        match *self {
+            ApplyError::ArgCount { .. } => "Wrong number of arguments",
+            ApplyError::ArgType { .. } => "Argument of the wrong type",
+            ApplyError::NotVariadic => "Function does not take variable arguments",
+            ApplyError::NoReturn => "Signature has no return type",
+            ApplyError::UnsupportedReturn(_) => "Unsupported return type",
+            ApplyError::Exception(_) => "Exception thrown by function"
+        }
+    }
+}
+
+/// Check the arguments given against the parameters of `signature`, allowing
+/// extra arguments if it is `variadic`
+pub fn check_args(signature: &Ty, args: &[DynValue], variadic: bool) -> Result<(), ApplyError> {
+    let num_params = signature.params().count();
+    if args.len() < num_params || !variadic && args.len() > num_params {
+        return Err(ApplyError::ArgCount {
+            expected: num_params,
+            got: args.len()
+        })
+    }
+    for (index, (arg, param)) in args.iter().zip(signature.params()).enumerate() {
+        if !arg.matches(param) {
+            return Err(ApplyError::ArgType {
+                index: index,
+                expected: param.to_owned(),
+                got: arg.get_type().into_owned()
+            })
+        }
+    }
+    Ok(())
+}
+
+/// Call `apply` with pointers to the arguments and a buffer for a value of
+/// `ret_type`, and return the value it puts in there
+///
+/// If `apply` returns false, the exception it threw is returned instead.
+/// LibJIT's handler for builtin exceptions is installed while it runs.
+pub fn apply_with<F>(ret_type: &Ty, args: &[DynValue], apply: F) -> Result<DynValue, ApplyError>
+    where F: FnOnce(*mut *mut c_void, *mut c_void) -> bool {
+    let mut ret = match DynValue::zeroed(ret_type) {
+        Some(ret) => ret,
+        None => return Err(ApplyError::UnsupportedReturn(ret_type.to_owned()))
+    };
+    let mut arg_ptrs: Vec<*mut c_void> = args.iter().map(DynValue::as_ptr).collect();
+    let old_handler = exception::install_handler();
+    exception::clear_last();
+    let succeeded = apply(arg_ptrs.as_mut_ptr(), ret.as_mut_ptr());
+    exception::restore_handler(old_handler);
+    if succeeded {
+        Ok(ret)
+    } else {
+        Err(ApplyError::Exception(exception::get_last_and_clear().unwrap_or(JitException::Thrown(ptr::null_mut()))))
+    }
+}
+
+/// Make the signature for calling a function with the variadic signature given
+/// with the arguments given, which includes the types of the variable arguments
+pub fn vararg_signature(signature: &Ty, args: &[DynValue]) -> Type {
+    let arg_types: Vec<_> = args.iter().map(DynValue::get_type).collect();
+    let mut params: Vec<&Ty> = arg_types.iter().map(|ty| &**ty).collect();
+    Type::new_signature(Abi::VarArg, signature.get_return().unwrap(), &mut params)
+}
+
+/// Call the native function at `func`, which has the signature given, with
+/// arguments whose types are only known at runtime, checking each of them
+/// against the signature
+///
+/// If the signature is variadic, any extra arguments are passed as its variable
+/// arguments. Calls that LibJIT supports making with arguments in its raw
+/// format are made that way, which avoids copying each argument separately.
+///
+/// ```rust
+/// use jit::*;
+/// extern fn add(x: i32, y: i32) -> i32 {
+///     x + y
+/// }
+/// let sig = get::<fn(i32, i32) -> i32>();
+/// let result = unsafe { call_native(add as *mut _, &sig, &[DynValue::Int(2), DynValue::Int(3)]) };
+/// assert_eq!(result, Ok(DynValue::Int(5)));
+/// ```
+pub unsafe fn call_native(func: *mut c_void, signature: &Ty, args: &[DynValue]) -> Result<DynValue, ApplyError> {
+    assert!(signature.is_signature(), "{:?} is not a signature", signature);
+    let variadic = jit_type_get_abi(signature.into()) == Abi::VarArg as jit_abi_t;
+    try!(check_args(signature, args, variadic));
+    let ret_type = match signature.get_return() {
+        Some(ret_type) => ret_type,
+        None => return Err(ApplyError::NoReturn)
+    };
+    let num_fixed = signature.params().count();
+    if variadic && args.len() > num_fixed {
+        let call_sig = vararg_signature(signature, args);
+        apply_with(ret_type, args, |args, ret| {
+            jit_apply((&*call_sig).into(), func, args, num_fixed as c_uint, ret);
+            exception::get_last().is_none()
+        })
+    } else if jit_raw_supported(signature.into()) != 0 {
+        let mut raw = Vec::new();
+        for (arg, param) in args.iter().zip(signature.params()) {
+            let size = param.get_size();
+            let words = (size + mem::size_of::<jit_nint>() - 1) / mem::size_of::<jit_nint>();
+            let start = raw.len();
+            raw.resize(start + words, 0 as jit_nint);
+            ptr::copy_nonoverlapping(arg.as_ptr() as *const u8, raw[start..].as_mut_ptr() as *mut u8, size);
+        }
+        apply_with(ret_type, &[], |_, ret| {
+            jit_apply_raw(signature.into(), func, raw.as_mut_ptr() as *mut c_void, ret);
+            exception::get_last().is_none()
+        })
+    } else {
+        apply_with(ret_type, args, |args, ret| {
+            jit_apply(signature.into(), func, args, num_fixed as c_uint, ret);
+            exception::get_last().is_none()
+        })
+    }
+}
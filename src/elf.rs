@@ -1,11 +1,13 @@
 use raw::*;
+use closure::Signature;
 use context::Context;
 use function::CompiledFunction;
+use types::{get, Ty, Type, TypeKind};
 use util::from_ptr;
 use 
-std::os::raw::{c_uint, c_char};
+std::os::raw::{c_char, c_int, c_uint, c_void};
 use std::ffi::{self, CString};
-use std::{fmt, str};
+use std::{fmt, slice, str};
 use std::marker::PhantomData;
 use std::{mem, ptr};
 use std::iter::Iterator;
@@ -49,25 +51,41 @@ impl<'a> Iterator for Needed<'a> {
         ((self.length - self.index) as usize, None)
     }
 }
+bitflags!(
+    /// Flags for opening an ELF binary with `ReadElf::with_flags`
+    pub struct ReadElfFlags: c_int {
+        /// Load the binary even if it wasn't written by LibJIT
+        const FORCE = 1;
+        /// Print debugging information while loading the binary
+        const DEBUG = 2;
+    }
+);
 /// An ELF binary reader
 pub struct ReadElf {
     _reader: jit_readelf_t
 }
 native_ref!(ReadElf, _reader: jit_readelf_t);
 #[repr(i32)]
-#[derive(Clone, Copy)]
-/// An error from trying to open the ELF
+#[derive(Clone, Copy, Eq, PartialEq)]
+/// An error from trying to open the ELF, or to load what is in it
 pub enum ReadElfErrorCode {
     /// The file couldn't be opened
-    CannotOpen,
+    CannotOpen = 1,
     /// The file isn't an ELF
-    NotElf,
+    NotElf = 2,
     /// The ELF is for a different architecture
-    WrongArch,
+    WrongArch = 3,
     /// The ELF is badly formatted
-    BadFormat,
+    BadFormat = 4,
     /// The ELF is too big to be loaded
-    Memory
+    Memory = 5,
+    /// Some of the symbols the ELFs in a context refer to couldn't be resolved
+    Unresolved = 6,
+    /// The ELF doesn't define the symbol asked for
+    NoSuchSymbol = 7,
+    /// The ELF was written with the function asked for having a different
+    /// signature
+    WrongSignature = 8
 }
 impl fmt::Display for ReadElfErrorCode {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
@@ -86,7 +104,10 @@ impl Error for ReadElfErrorCode {
             ReadElfErrorCode::NotElf => "Not an ELF-format binary",
             ReadElfErrorCode::WrongArch => "Wrong architecture for local system",
             ReadElfErrorCode::BadFormat => "ELF file, but badly formatted",
-            ReadElfErrorCode::Memory => "Insufficient memory to load the file"
+            ReadElfErrorCode::Memory => "Insufficient memory to load the file",
+            ReadElfErrorCode::Unresolved => "Could not resolve all of the symbols",
+            ReadElfErrorCode::NoSuchSymbol => "No such symbol in the file",
+            ReadElfErrorCode::WrongSignature => "Function was written with a different signature"
         }
     }
 }
@@ -94,25 +115,58 @@ impl Error for ReadElfErrorCode {
 /// An error from trying to open the ELF, including the filename
 pub struct ReadElfError<'a> {
     filename: &'a str,
+    symbol: Option<&'a str>,
     error: ReadElfErrorCode
 }
+impl<'a> ReadElfError<'a> {
+    /// Get the name of the file the error came from
+    pub fn get_filename(&self) -> &'a str {
+        self.filename
+    }
+    /// Get the symbol that couldn't be loaded, if the error was about one
+    pub fn get_symbol(&self) -> Option<&'a str> {
+        self.symbol
+    }
+    /// Get the kind of error this is
+    pub fn get_code(&self) -> ReadElfErrorCode {
+        self.error
+    }
+}
+impl<'a> fmt::Display for ReadElfError<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.symbol {
+            Some(symbol) => write!(fmt, "'{}': '{}': {}", self.filename, symbol, self.error),
+            None => write!(fmt, "'{}': {}", self.filename, self.error)
+        }
+    }
+}
 impl<'a> fmt::Debug for ReadElfError<'a> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        write!(fmt, "'{}': {}", self.filename, self.error)
+        fmt::Display::fmt(self, fmt)
+    }
+}
+impl<'a> Error for ReadElfError<'a> {
+    fn description(&self) -> &str {
+        self.error.description()
     }
 }
 impl ReadElf {
     /// Open a new ELF binary
     pub fn new(filename:&str) -> Result<ReadElf, ReadElfError> {
+        ReadElf::with_flags(filename, ReadElfFlags::empty())
+    }
+    /// Open a new ELF binary with the flags given
+    pub fn with_flags(filename:&str, flags: ReadElfFlags) -> Result<ReadElf, ReadElfError> {
         unsafe {
             let mut this = ptr::null_mut();
             let c_name = CString::new(filename.as_bytes()).unwrap();
-            let code = jit_readelf_open(&mut this, mem::transmute(c_name.as_bytes().as_ptr()), 0);
+            let code = jit_readelf_open(&mut this, mem::transmute(c_name.as_bytes().as_ptr()), flags.bits());
             if code == 0 {
                 Ok(from_ptr(this))
             } else {
                 Err(ReadElfError {
                     filename: filename,
+                    symbol: None,
                     error: mem::transmute(code)
                 })
             }
@@ -138,11 +192,114 @@ impl ReadElf {
         let c_sym = CString::new(symbol.as_bytes()).unwrap();
         mem::transmute(jit_readelf_get_symbol(self.into(), c_sym.as_bytes().as_ptr() as *const c_char))
     }
+    /// Get the function called `name` in the ELF binary as a native function
+    /// pointer with the signature `S`
+    ///
+    /// If a signature was recorded for `name` with `WriteElf::add_signature`,
+    /// `S` is checked against it. The ELF must have been added
+    /// to a context, and its symbols resolved with `ReadElf::resolve_all`,
+    /// before the function is called.
+    pub fn get_function<'a, S>(&'a self, name: &'a str) -> Result<S::Extern, ReadElfError<'a>> where S: Signature {
+        let error = |code| ReadElfError {
+            filename: self.get_name(),
+            symbol: Some(name),
+            error: code
+        };
+        if let Some(written) = self.get_section(SIGNATURES).and_then(|section| find_signature(section, name)) {
+            let mut expected = Vec::new();
+            if !encode_type(&get::<S>(), &mut expected) || expected != written {
+                return Err(error(ReadElfErrorCode::WrongSignature))
+            }
+        }
+        let c_name = CString::new(name.as_bytes()).unwrap();
+        let ptr = unsafe { jit_readelf_get_symbol(self.into(), c_name.as_ptr()) };
+        if ptr.is_null() {
+            Err(error(ReadElfErrorCode::NoSuchSymbol))
+        } else {
+            Ok(unsafe { mem::transmute_copy(&ptr) })
+        }
+    }
+    /// Get the signature recorded for `name` with `WriteElf::add_signature`,
+    /// if there is one
+    pub fn get_signature(&self, name: &str) -> Option<Type> {
+        self.get_section(SIGNATURES)
+            .and_then(|section| find_signature(section, name))
+            .and_then(|mut written| decode_type(&mut written).ok())
+    }
+    /// Get the contents of the section called `name`, if there is one
+    pub fn get_section(&self, name: &str) -> Option<&[u8]> {
+        let c_name = CString::new(name.as_bytes()).unwrap();
+        unsafe {
+            let mut size = 0;
+            let data = jit_readelf_get_section(self.into(), c_name.as_ptr(), &mut size);
+            from_section(data, size)
+        }
+    }
+    /// Get the contents of the first section with the ELF section type given,
+    /// such as 6 for `SHT_DYNAMIC`, if there is one
+    pub fn get_section_by_type(&self, ty: i32) -> Option<&[u8]> {
+        unsafe {
+            let mut size = 0;
+            let data = jit_readelf_get_section_by_type(self.into(), ty, &mut size);
+            from_section(data, size)
+        }
+    }
+    /// Map a virtual address in the ELF binary to where it was loaded in
+    /// memory, if it is inside the binary
+    pub fn map_vaddr(&self, vaddr: usize) -> Option<*mut c_void> {
+        unsafe {
+            let ptr = jit_readelf_map_vaddr(self.into(), vaddr as jit_nuint);
+            if ptr.is_null() {
+                None
+            } else {
+                Some(ptr)
+            }
+        }
+    }
     #[inline]
     /// Iterate over the needed libraries
     pub fn needed(&self) -> Needed {
         Needed::new(self)
     }
+    /// Resolve the symbols and perform the relocations of every ELF binary
+    /// that has been added to `ctx`, using the symbols they define and the
+    /// symbols registered with `ReadElf::register_symbol`
+    ///
+    /// If `print_failures` is set, the symbols that couldn't be resolved are
+    /// printed to standard error.
+    pub fn resolve_all(ctx: &Context, print_failures: bool) -> Result<(), ReadElfErrorCode> {
+        unsafe {
+            if jit_readelf_resolve_all(ctx.into(), print_failures as c_int) == 0 {
+                Ok(())
+            } else {
+                Err(ReadElfErrorCode::Unresolved)
+            }
+        }
+    }
+    /// Register a symbol from the host program, so that ELF binaries added to
+    /// `ctx` can refer to it
+    ///
+    /// If `after` is set, symbols the ELF binaries define themselves take
+    /// precedence over this one.
+    pub fn register_symbol(ctx: &Context, name: &str, value: *mut c_void, after: bool) -> Result<(), ReadElfErrorCode> {
+        let c_name = CString::new(name.as_bytes()).unwrap();
+        unsafe {
+            if jit_readelf_register_symbol(ctx.into(), c_name.as_ptr(), value, after as c_int) == 0 {
+                Err(ReadElfErrorCode::Memory)
+            } else {
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Wrap the contents of a section LibJIT found, if it found one
+unsafe fn from_section<'a>(data: *mut c_void, size: jit_nuint) -> Option<&'a [u8]> {
+    if data.is_null() {
+        None
+    } else {
+        Some(slice::from_raw_parts(data as *const u8, size as usize))
+    }
 }
 impl Drop for ReadElf {
     #[inline]
@@ -153,14 +310,20 @@ impl Drop for ReadElf {
     }
 }
 
-/// An ELF binary reader
+/// An ELF binary writer
+///
+/// This can't produce ahead-of-time compiled binaries. LibJIT leaves
+/// `jit_writeelf_add_function` unimplemented, and its code generators don't
+/// record the relocations compiled code would need to be loaded into another
+/// process, so a binary this writes only holds the libraries it needs and
+/// the signatures recorded with `add_signature`, not code that can be called.
 pub struct WriteElf {
     _writer: jit_writeelf_t
 }
 native_ref!(WriteElf, _writer: jit_writeelf_t);
 impl WriteElf {
     #[inline]
-    /// Create a new ELF binary reader
+    /// Create a new ELF binary writer for the library named `lib_name`
     pub fn new(lib_name:&str) -> WriteElf {
         unsafe {
             let c_lib = CString::new(lib_name.as_bytes()).unwrap();
@@ -168,8 +331,8 @@ impl WriteElf {
         }
     }
     #[inline]
-    /// Write to the filename given (not implemented by LibJIT yet, so there's no point to this yet
-    /// but I'm sure GNU will hear the people sing the songs of angry men soon enough)
+    /// Write the ELF binary to the filename given, returning `false` if it
+    /// couldn't be written
     pub fn write(&self, filename:&str) -> bool {
         unsafe {
             let c_filename = CString::new(filename.as_bytes()).unwrap();
@@ -178,14 +341,35 @@ impl WriteElf {
     }
     #[inline]
     /// Add a function to the ELF
+    ///
+    /// LibJIT doesn't implement this yet, so this does nothing and the
+    /// function's code isn't written out.
     pub fn add_function(&self, func:&CompiledFunction, name:&str) -> bool {
         unsafe {
             let c_name = CString::new(name.as_bytes()).unwrap();
             jit_writeelf_add_function(self.into(), func.into(), c_name.as_bytes().as_ptr() as *const c_char) != 0
         }
     }
+    /// Record `signature` as the signature of the function called `name`, so
+    /// `ReadElf::get_function` can check it when the ELF is loaded
+    ///
+    /// This only writes the signature, not any code. It returns `false` if
+    /// the signature has tagged types with data attached, which can't be
+    /// written out, or if LibJIT ran out of memory.
+    pub fn add_signature(&self, name:&str, signature:&Ty) -> bool {
+        let c_name = CString::new(name.as_bytes()).unwrap();
+        let mut entry = c_name.as_bytes_with_nul().to_vec();
+        if !encode_type(signature, &mut entry) {
+            return false
+        }
+        unsafe {
+            let c_section = CString::new(SIGNATURES).unwrap();
+            jit_writeelf_write_section(self.into(), c_section.as_ptr(), SHT_PROGBITS, entry.as_ptr() as *const c_void, entry.len() as c_uint, 0) != 0
+        }
+    }
     #[inline]
-    /// Add a dependency to the ELF
+    /// Add a dependency to the ELF, which `ReadElf::needed` lists when it is
+    /// loaded
     pub fn add_needed(&self, lib_name:&str) -> bool {
         unsafe {
             let c_lib = CString::new(lib_name.as_bytes()).unwrap();
@@ -201,3 +385,134 @@ impl Drop for WriteElf {
         }
     }
 }
+
+/// The section `WriteElf::add_signature` records the name and signature of each function in,
+/// as the name followed by a nul and then the signature as `encode_type`
+/// writes it
+const SIGNATURES: &'static str = ".jitsigs";
+/// The ELF section type for data that only means something to the program
+const SHT_PROGBITS: jit_int = 1;
+/// The kind `encode_type` writes before a tagged type
+const TAGGED: c_int = -1;
+
+/// Find the encoded signature of the function called `name` in the
+/// signatures section
+fn find_signature<'a>(section: &'a [u8], name: &str) -> Option<&'a [u8]> {
+    let mut rest = section;
+    while let Some(end) = rest.iter().position(|&byte| byte == 0) {
+        let entry = &rest[..end];
+        let start = &rest[end + 1..];
+        rest = start;
+        if decode_type(&mut rest).is_err() {
+            return None
+        }
+        if entry == name.as_bytes() {
+            return Some(&start[..start.len() - rest.len()])
+        }
+    }
+    None
+}
+
+/// Write an integer to `buf` as four little-endian bytes so `take_int` can
+/// read it back
+fn push_int(buf: &mut Vec<u8>, int: c_int) {
+    for byte in 0..4 {
+        buf.push((int >> (byte * 8)) as u8)
+    }
+}
+/// Read an integer written by `push_int` from the start of `data`
+fn take_int(data: &mut &[u8]) -> Result<c_int, ReadElfErrorCode> {
+    if data.len() < 4 {
+        return Err(ReadElfErrorCode::BadFormat)
+    }
+    let (int, rest) = data.split_at(4);
+    *data = rest;
+    Ok(int.iter().rev().fold(0, |int, &byte| int << 8 | byte as c_int))
+}
+
+/// Write `ty` to `buf` as its kind followed by the types it is made of, or
+/// return `false` if it has tagged data that can't be written out
+fn encode_type(ty: &Ty, buf: &mut Vec<u8>) -> bool {
+    unsafe {
+        let raw: jit_type_t = ty.into();
+        if jit_type_is_tagged(raw) != 0 {
+            if !jit_type_get_tagged_data(raw).is_null() {
+                return false
+            }
+            push_int(buf, TAGGED);
+            push_int(buf, jit_type_get_tagged_kind(raw));
+            return encode_type(from_ptr(jit_type_get_tagged_type(raw)), buf)
+        }
+        push_int(buf, jit_type_get_kind(raw));
+        if jit_type_is_pointer(raw) != 0 {
+            encode_type(from_ptr(jit_type_get_ref(raw)), buf)
+        } else if jit_type_is_struct(raw) != 0 || jit_type_is_union(raw) != 0 {
+            let count = jit_type_num_fields(raw);
+            push_int(buf, count as c_int);
+            (0..count).all(|index| encode_type(from_ptr(jit_type_get_field(raw, index)), buf))
+        } else if jit_type_is_signature(raw) != 0 {
+            push_int(buf, jit_type_get_abi(raw) as c_int);
+            let count = jit_type_num_params(raw);
+            push_int(buf, count as c_int);
+            encode_type(from_ptr(jit_type_get_return(raw)), buf)
+                && (0..count).all(|index| encode_type(from_ptr(jit_type_get_param(raw, index)), buf))
+        } else {
+            true
+        }
+    }
+}
+/// Rebuild a type written by `encode_type` from the start of `data`
+fn decode_type(data: &mut &[u8]) -> Result<Type, ReadElfErrorCode> {
+    let kind = try!(take_int(data));
+    unsafe {
+        if kind == TAGGED {
+            let tag = try!(take_int(data));
+            let ty = try!(decode_type(data));
+            return Ok(from_ptr(jit_type_create_tagged((&ty).into(), tag, ptr::null_mut(), None, 1)))
+        }
+        let kind = TypeKind::from_bits_truncate(kind);
+        if kind == TypeKind::Pointer {
+            let pointee = try!(decode_type(data));
+            Ok(Type::new_pointer(&pointee))
+        } else if kind == TypeKind::Struct || kind == TypeKind::Union {
+            let count = try!(take_int(data));
+            let fields: Vec<Type> = try!((0..count).map(|_| decode_type(data)).collect());
+            let fields: Vec<&Ty> = fields.iter().map(|field| &**field).collect();
+            Ok(if kind == TypeKind::Struct {
+                Type::new_struct(&fields)
+            } else {
+                Type::new_union(&fields)
+            })
+        } else if kind == TypeKind::Signature {
+            let abi = try!(take_int(data));
+            let count = try!(take_int(data));
+            let ret = try!(decode_type(data));
+            let params: Vec<Type> = try!((0..count).map(|_| decode_type(data)).collect());
+            let mut params: Vec<jit_type_t> = params.iter().map(|param| (&**param).into()).collect();
+            Ok(from_ptr(jit_type_create_signature(abi as jit_abi_t, (&*ret).into(), params.as_mut_ptr(), count as c_uint, 1)))
+        } else {
+            primitive(kind).map(|ty| ty.to_owned()).ok_or(ReadElfErrorCode::BadFormat)
+        }
+    }
+}
+/// Get LibJIT's type for the primitive kind given
+fn primitive(kind: TypeKind) -> Option<&'static Ty> {
+    use types::consts::*;
+    let primitives = [
+        (TypeKind::Void, get_void()),
+        (TypeKind::SByte, get_sbyte()),
+        (TypeKind::UByte, get_ubyte()),
+        (TypeKind::Short, get_short()),
+        (TypeKind::UShort, get_ushort()),
+        (TypeKind::Int, get_int()),
+        (TypeKind::UInt, get_uint()),
+        (TypeKind::NInt, get_nint()),
+        (TypeKind::NUInt, get_nuint()),
+        (TypeKind::Long, get_long()),
+        (TypeKind::ULong, get_ulong()),
+        (TypeKind::Float32, get_float32()),
+        (TypeKind::Float64, get_float64()),
+        (TypeKind::NFloat, get_nfloat())
+    ];
+    primitives.iter().find(|&&(prim, _)| prim == kind).map(|&(_, ty)| ty)
+}
@@ -0,0 +1,265 @@
+//! Catching exceptions thrown by JIT-compiled code
+//!
+//! Exceptions are raised inside compiled code either explicitly with
+//! `insn_throw`, or by LibJIT itself when a checked instruction like
+//! `insn_check_null` or `insn_add_ovf` fails. The latter are called builtin
+//! exceptions, and are represented by `BuiltinException`.
+use raw::*;
+use std::os::raw::{c_int, c_void};
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::RwLock;
+use std::{fmt, mem};
+
+/// A kind of exception raised by LibJIT itself rather than by `insn_throw`
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinException {
+    /// An arithmetic operation overflowed
+    Overflow,
+    /// An arithmetic operation failed
+    Arithmetic,
+    /// A number was divided by zero
+    DivisionByZero,
+    /// A function could not be compiled
+    CompileError,
+    /// There was not enough memory to continue
+    OutOfMemory,
+    /// A null pointer was dereferenced
+    NullReference,
+    /// A null function pointer was called
+    NullFunction,
+    /// A nested function was called from something other than its parent
+    CalledNested,
+    /// An index was out of bounds
+    OutOfBounds,
+    /// A branch was made to a label that was never placed
+    UndefinedLabel,
+    /// The code memory is full
+    MemoryFull,
+    /// A builtin exception with a code this crate doesn't know about
+    Unknown(c_int)
+}
+/// Every builtin exception this crate knows about, in order of their codes
+static BUILTINS: [BuiltinException; 11] = [
+    BuiltinException::Overflow,
+    BuiltinException::Arithmetic,
+    BuiltinException::DivisionByZero,
+    BuiltinException::CompileError,
+    BuiltinException::OutOfMemory,
+    BuiltinException::NullReference,
+    BuiltinException::NullFunction,
+    BuiltinException::CalledNested,
+    BuiltinException::OutOfBounds,
+    BuiltinException::UndefinedLabel,
+    BuiltinException::MemoryFull
+];
+/// The codes of `BUILTINS`
+///
+/// The handler installed by `install_handler` throws a pointer into this
+/// array, so builtin exceptions can be told apart from thrown values.
+static CODES: [c_int; 11] = [0, -1, -2, -3, -4, -5, -6, -7, -8, -9, -10000];
+/// The codes of the unknown builtin exceptions that have been thrown, boxed
+/// so each has an address to throw like the ones in `CODES`
+///
+/// There is one for each distinct code, and they live as long as the process,
+/// since any of them could still be caught.
+struct Unknown {
+    /// The boxed code for each code
+    codes: HashMap<c_int, Box<c_int>>,
+    /// The code at each boxed code's address
+    addrs: HashMap<usize, c_int>
+}
+/// The unknown codes, which are only made once one is thrown, since a
+/// `HashMap` can't be made in a static
+static UNKNOWN: RwLock<Option<Unknown>> = RwLock::new(None);
+impl BuiltinException {
+    /// Get the builtin exception with the LibJIT code given
+    pub fn from_code(code: c_int) -> BuiltinException {
+        match CODES.iter().position(|&known| known == code) {
+            Some(index) => BUILTINS[index],
+            None => BuiltinException::Unknown(code)
+        }
+    }
+    /// Get the LibJIT code of this builtin exception
+    pub fn get_code(&self) -> c_int {
+        match *self {
+            BuiltinException::Unknown(code) => code,
+            kind => CODES[BUILTINS.iter().position(|&known| known == kind).unwrap()]
+        }
+    }
+    fn from_ptr(ptr: *mut c_void) -> Option<BuiltinException> {
+        let start = CODES.as_ptr() as usize;
+        let end = start + mem::size_of_val(&CODES);
+        let addr = ptr as usize;
+        if addr >= start && addr < end {
+            Some(BUILTINS[(addr - start) / mem::size_of::<c_int>()])
+        } else {
+            UNKNOWN.read().unwrap().as_ref()
+                .and_then(|unknown| unknown.addrs.get(&addr))
+                .map(|&code| BuiltinException::Unknown(code))
+        }
+    }
+    fn as_ptr(&self) -> *mut c_void {
+        let code = self.get_code();
+        if let Some(index) = CODES.iter().position(|&known| known == code) {
+            return &CODES[index] as *const c_int as *mut c_void
+        }
+        if let Some(boxed) = UNKNOWN.read().unwrap().as_ref().and_then(|unknown| unknown.codes.get(&code)) {
+            return &**boxed as *const c_int as *mut c_void
+        }
+        let mut unknown = UNKNOWN.write().unwrap();
+        let unknown = unknown.get_or_insert_with(|| Unknown {
+            codes: HashMap::new(),
+            addrs: HashMap::new()
+        });
+        // Another thread may have interned the code since it was looked up
+        let boxed = unknown.codes.entry(code).or_insert_with(|| Box::new(code));
+        let ptr = &**boxed as *const c_int as *mut c_void;
+        unknown.addrs.insert(ptr as usize, code);
+        ptr
+    }
+}
+impl fmt::Display for BuiltinException {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BuiltinException::Unknown(code) => write!(fmt, "{} ({})", self.description(), code),
+            _ => write!(fmt, "{}", self.description())
+        }
+    }
+}
+impl fmt::Debug for BuiltinException {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, fmt)
+    }
+}
+impl Error for BuiltinException {
+    fn description(&self) -> &'static str {
+        match *self {
+            BuiltinException::Overflow => "Overflow during checked arithmetic operation",
+            BuiltinException::Arithmetic => "Arithmetic exception (dividing the minimum integer by -1)",
+            BuiltinException::DivisionByZero => "Division by zero",
+            BuiltinException::CompileError => "Error during function compilation",
+            BuiltinException::OutOfMemory => "Out of memory",
+            BuiltinException::NullReference => "Null pointer dereferenced",
+            BuiltinException::NullFunction => "Null function pointer called",
+            BuiltinException::CalledNested => "Nested function called from non-nested context",
+            BuiltinException::OutOfBounds => "Array index out of bounds",
+            BuiltinException::UndefinedLabel => "Undefined label",
+            BuiltinException::MemoryFull => "Memory is full",
+            BuiltinException::Unknown(_) => "Unknown builtin exception"
+        }
+    }
+}
+
+/// An exception that escaped from JIT-compiled code
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum JitException {
+    /// An exception raised by LibJIT itself
+    Builtin(BuiltinException),
+    /// The value given to `insn_throw`
+    Thrown(*mut c_void)
+}
+impl JitException {
+    /// Classify the raw exception object given
+    pub fn from_ptr(ptr: *mut c_void) -> JitException {
+        match BuiltinException::from_ptr(ptr) {
+            Some(kind) => JitException::Builtin(kind),
+            None => JitException::Thrown(ptr)
+        }
+    }
+    /// Get the raw exception object that LibJIT would throw for this exception
+    pub fn as_ptr(&self) -> *mut c_void {
+        match *self {
+            JitException::Builtin(kind) => kind.as_ptr(),
+            JitException::Thrown(ptr) => ptr
+        }
+    }
+}
+impl fmt::Display for JitException {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            JitException::Builtin(kind) => write!(fmt, "{}", kind),
+            JitException::Thrown(ptr) => write!(fmt, "Exception thrown with value {:?}", ptr)
+        }
+    }
+}
+impl fmt::Debug for JitException {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            JitException::Builtin(kind) => write!(fmt, "Builtin({:?})", kind),
+            JitException::Thrown(ptr) => write!(fmt, "Thrown({:?})", ptr)
+        }
+    }
+}
+impl Error for JitException {
+    fn description(&self) -> &str {
+        match *self {
+            JitException::Builtin(ref kind) => kind.description(),
+            JitException::Thrown(_) => "Exception thrown by JIT code"
+        }
+    }
+}
+
+extern fn builtin_handler(code: c_int) -> *mut c_void {
+    BuiltinException::from_code(code).as_ptr()
+}
+
+/// Install a handler on the current thread that turns builtin exceptions into
+/// thrown objects that `JitException` can recognise, returning the old handler
+///
+/// Without a handler, LibJIT aborts the process when a builtin exception occurs.
+pub fn install_handler() -> jit_exception_func {
+    unsafe {
+        jit_exception_set_handler(Some(builtin_handler))
+    }
+}
+/// Restore the exception handler returned by `install_handler`
+pub fn restore_handler(handler: jit_exception_func) {
+    unsafe {
+        jit_exception_set_handler(handler);
+    }
+}
+/// Get the last exception thrown on the current thread without clearing it
+pub fn get_last() -> Option<JitException> {
+    unsafe {
+        let ptr = jit_exception_get_last();
+        if ptr.is_null() {
+            None
+        } else {
+            Some(JitException::from_ptr(ptr))
+        }
+    }
+}
+/// Get the last exception thrown on the current thread and clear it
+pub fn get_last_and_clear() -> Option<JitException> {
+    unsafe {
+        let ptr = jit_exception_get_last_and_clear();
+        if ptr.is_null() {
+            None
+        } else {
+            Some(JitException::from_ptr(ptr))
+        }
+    }
+}
+/// Clear the last exception thrown on the current thread
+pub fn clear_last() {
+    unsafe {
+        jit_exception_clear_last()
+    }
+}
+/// Throw an exception from Rust code that has been called by JIT code
+///
+/// This unwinds straight through the Rust frames in between without running
+/// their destructors, so it should only be called from a function given to
+/// `insn_call_rust` or `insn_call_native`.
+pub unsafe fn throw(exception: JitException) -> ! {
+    jit_exception_throw(exception.as_ptr());
+    unreachable!()
+}
+/// Raise a builtin exception from Rust code that has been called by JIT code
+///
+/// The same restrictions as `throw` apply.
+pub unsafe fn throw_builtin(kind: BuiltinException) -> ! {
+    jit_exception_builtin(kind.get_code());
+    unreachable!()
+}
@@ -1,11 +1,15 @@
 use raw::*;
 use context::{Context, ContextMember};
+use dynamic::{self, ApplyError, DynValue};
 use compile::Compile;
+use exception::{self, BuiltinException, JitException};
 use label::Label;
-use types::{Ty, Type};
-use insn::Block;
+use source::{SourceLocation, SourceMap};
+use types::{consts, Ty, Type, TypeKind};
+use insn::{Block, Blocks};
 use value::Val;
-use util::{self, CString, from_ptr, from_ptr_opt};
+use verify::{self, VerifyError};
+use util::{self, CString, CATCHER_META, JIT_RESULT_OK, ON_DEMAND_META, SOURCE_MAP_META, from_ptr, from_ptr_opt, oom};
 use cbox::{CSemiBox, DisposeRef};
 use std::os::raw::{
     c_int,
@@ -14,6 +18,7 @@ use std::os::raw::{
 };
 use std::any::Any;
 use std::default::Default;
+use std::error::Error;
 use std::fmt;
 use std::ops::{Deref, DerefMut, Index};
 use std::{mem, ptr};
@@ -70,6 +75,73 @@ impl Func {
     pub fn get_signature(&self) -> &Ty {
         unsafe { from_ptr(jit_function_get_signature(self.into())) }
     }
+    /// Get the source map built with `insn_mark_location`, if any locations
+    /// have been marked in this function
+    pub fn get_source_map(&self) -> Option<&SourceMap> {
+        unsafe {
+            let meta = jit_function_get_meta(self.into(), SOURCE_MAP_META);
+            if meta.is_null() {
+                None
+            } else {
+                Some(mem::transmute(meta))
+            }
+        }
+    }
+    /// Get the source location that was marked with the offset given
+    pub fn get_location(&self, offset: u32) -> Option<&SourceLocation> {
+        self.get_source_map().and_then(|map| map.get(offset))
+    }
+    #[inline(always)]
+    /// Check if this function is a candidate for recompilation
+    pub fn is_recompilable(&self) -> bool {
+        unsafe { jit_function_is_recompilable(self.into()) != 0 }
+    }
+    #[inline(always)]
+    /// Get the optimization level this function is compiled at
+    pub fn get_optimization_level(&self) -> c_uint {
+        unsafe { jit_function_get_optimization_level(self.into()) }
+    }
+    #[inline(always)]
+    /// Make calls to this function run the code at `entry`, which should have
+    /// come from `UncompiledFunction::compile_entry`
+    ///
+    /// The build lock should be held while this is done, so no other thread
+    /// sees the function half-swapped.
+    pub unsafe fn setup_entry(&self, entry: *mut c_void) {
+        jit_function_setup_entry(self.into(), entry)
+    }
+    #[inline(always)]
+    /// Get the pointer to this function to store in vtables, which should be
+    /// called with `UncompiledFunction::insn_call_indirect_vtable`
+    ///
+    /// This is the same as the closure pointer unless the interpreter is used.
+    pub fn to_vtable_pointer(&self) -> *mut c_void {
+        unsafe { jit_function_to_vtable_pointer(self.into()) }
+    }
+    /// Build this function with its on-demand compiler and compile it, if it
+    /// hasn't been compiled yet, then get its entry point
+    ///
+    /// This is what LibJIT does by default when a function made with
+    /// `CompiledFunction::new_on_demand` is first called, so it is useful for
+    /// drivers given to `Context::set_on_demand_driver`.
+    ///
+    /// This doesn't take the build lock of the context, because LibJIT
+    /// already holds it while it runs the driver. Anywhere else, it must be
+    /// called inside `Context::build`.
+    pub fn compile_on_demand(&self) -> Option<*mut c_void> {
+        unsafe {
+            let func = self.into();
+            let compiled = jit_function_is_compiled(func) != 0 || match jit_function_get_on_demand_compiler(func) {
+                Some(on_demand) => on_demand(func) == JIT_RESULT_OK && jit_function_compile(func) != 0,
+                None => false
+            };
+            if compiled {
+                Some(jit_function_to_closure(func))
+            } else {
+                None
+            }
+        }
+    }
 }
 
 /// A function which has already been compiled from an `UncompiledFunction`, so it can
@@ -77,8 +149,12 @@ impl Func {
 ///
 /// A function persists for the lifetime of its containing context. This is
 /// a function which has already been compiled and is now in executable form.
+///
+/// This can't be shared with other threads, since it belongs to a context
+/// that might not have thread support. To call it from other threads, send
+/// them the native function pointer given by `as_func` instead.
 #[derive(Clone, Copy)]
-pub struct CompiledFunction(());
+pub struct CompiledFunction(PhantomData<*mut ()>);
 native_ref!(&CompiledFunction = jit_function_t);
 impl DisposeRef for CompiledFunction {
     type RefTo = Struct__jit_function;
@@ -92,14 +168,77 @@ impl Deref for CompiledFunction {
         unsafe { mem::transmute(self) }
     }
 }
-impl fmt::Debug for CompiledFunction {
+impl fmt::Display for CompiledFunction {
+    /// Write the disassembly of this function if it is available, or its
+    /// instructions if it isn't
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         write!(fmt, "{}", try!(util::dump(|fd| unsafe {
-            jit_dump_function(mem::transmute(fd), self.into(), ptr::null());
+            jit_dump_function(fd as *mut c_void, self.into(), ptr::null());
         })))
     }
 }
+impl fmt::Debug for CompiledFunction {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, fmt)
+    }
+}
 impl CompiledFunction {
+    /// Create a function with the signature given that will be built by
+    /// `builder` and compiled the first time it is called, rather than straight away
+    ///
+    /// ```rust
+    /// use jit::*;
+    /// let mut ctx = Context::<()>::new();
+    /// let func = CompiledFunction::new_on_demand(&mut ctx, &get::<fn(i32) -> i32>(), |func| {
+    ///     let x = &func[0];
+    ///     func.insn_return(func.insn_mul(x, x));
+    /// });
+    /// assert!(!func.is_compiled());
+    /// let square: extern fn(i32) -> i32 = func.as_func();
+    /// assert_eq!(square(7), 49);
+    /// assert!(func.is_compiled());
+    /// ```
+    pub fn new_on_demand<'a, T, F>(context:&'a Context<T>, signature:&Ty, builder: F) -> CSemiBox<'a, CompiledFunction>
+        where F: Fn(&UncompiledFunction) + 'static {
+        unsafe {
+            let func = jit_function_create(context.into(), signature.into());
+            let builder: Box<Box<Fn(&UncompiledFunction)>> = Box::new(Box::new(builder));
+            if jit_function_set_meta(func, ON_DEMAND_META, mem::transmute(builder), Some(::free_data::<Box<Fn(&UncompiledFunction)>>), 1) == 0 {
+                oom()
+            }
+            jit_function_set_on_demand_compiler(func, Some(build_on_demand));
+            CSemiBox::new(func)
+        }
+    }
+    /// Rebuild the body of this recompilable function with `builder`, compile
+    /// it at the optimization level given, then swap the new code in
+    ///
+    /// Closures made with `as_func` before this is called stay valid, and
+    /// call the new code once this returns. This returns false and keeps the
+    /// old code if the new code failed to compile, throwing away what
+    /// `builder` made so the next recompile starts from scratch.
+    ///
+    /// This holds the build lock of the context while `builder` runs, which
+    /// is released even if it panics.
+    pub fn recompile<F>(&self, level: c_uint, builder: F) -> bool where F: FnOnce(&UncompiledFunction) {
+        debug_assert!(self.is_recompilable(), "{:?} must be made recompilable before it is compiled", self.get_signature());
+        let _lock = self.get_context().lock_build();
+        let raw: jit_function_t = self.into();
+        let func: &UncompiledFunction = from_ptr(raw);
+        func.set_optimization_level(level);
+        builder(func);
+        match func.compile_entry() {
+            Some(entry) => unsafe {
+                self.setup_entry(entry);
+                true
+            },
+            None => unsafe {
+                // This only frees the builder, since the old code is still in use
+                jit_function_abandon(raw);
+                false
+            }
+        }
+    }
     /// Retrieve this function's compiled form so it can be called
     pub fn as_func<'a, A, R>(&self) -> extern fn(A) -> R where A:Compile<'a>, R: Compile<'a> {
         util::assert_sig::<A, R>(&self.get_signature());
@@ -109,6 +248,79 @@ impl CompiledFunction {
     }
     /// Run the compiled function with several arguments.
     pub fn apply<'a, R>(&'a self, args: &[&Any]) -> R where R: Compile<'a> + Default {
+        let mut ret: R = R::default();
+        self.apply_into(args, &mut ret);
+        ret
+    }
+    /// Run the compiled function with several arguments, and return any
+    /// exception it throws as an error instead of its return value.
+    ///
+    /// This installs the handler from `exception::install_handler` for the
+    /// duration of the call, so builtin exceptions such as division by zero
+    /// are caught too.
+    ///
+    /// ```rust
+    /// use jit::*;
+    /// use jit::exception::{BuiltinException, JitException};
+    /// let mut ctx = Context::<()>::new();
+    /// let func = UncompiledFunction::new(&mut ctx, &get::<fn(*const i32) -> i32>());
+    /// let ptr = &func[0];
+    /// func.insn_check_null(ptr);
+    /// func.insn_return(func.insn_load_relative(ptr, 0, &get::<i32>()));
+    /// let func = UncompiledFunction::compile(func).unwrap();
+    /// let null: *const i32 = std::ptr::null();
+    /// let err = func.try_apply::<i32>(&[&null]).unwrap_err();
+    /// assert_eq!(err, JitException::Builtin(BuiltinException::NullReference));
+    /// ```
+    pub fn try_apply<'a, R>(&'a self, args: &[&Any]) -> Result<R, JitException> where R: Compile<'a> + Default {
+        let mut ret: R = R::default();
+        let old_handler = exception::install_handler();
+        exception::clear_last();
+        let succeeded = self.apply_into(args, &mut ret);
+        exception::restore_handler(old_handler);
+        if succeeded {
+            Ok(ret)
+        } else {
+            Err(exception::get_last_and_clear().unwrap_or(JitException::Thrown(ptr::null_mut())))
+        }
+    }
+    /// Run the compiled function with arguments whose types are only known at
+    /// runtime, checking each of them against its signature
+    ///
+    /// ```rust
+    /// use jit::*;
+    /// let mut ctx = Context::<()>::new();
+    /// let func = UncompiledFunction::new(&mut ctx, &get::<fn(i32, f64) -> f64>());
+    /// let x = func.insn_convert(&func[0], &get::<f64>(), false);
+    /// func.insn_return(func.insn_mul(x, &func[1]));
+    /// let func = UncompiledFunction::compile(func).unwrap();
+    /// let result = func.apply_dyn(&[DynValue::Int(3), DynValue::Float64(1.5)]);
+    /// assert_eq!(result, Ok(DynValue::Float64(4.5)));
+    /// assert!(func.apply_dyn(&[DynValue::Int(3)]).is_err());
+    /// ```
+    pub fn apply_dyn(&self, args: &[DynValue]) -> Result<DynValue, ApplyError> {
+        let sig = self.get_signature();
+        try!(dynamic::check_args(sig, args, false));
+        let ret_type = try!(sig.get_return().ok_or(ApplyError::NoReturn));
+        dynamic::apply_with(ret_type, args, |args, ret| unsafe {
+            jit_function_apply(self.into(), args, ret) != 0
+        })
+    }
+    /// Run this compiled function, which must have a variadic signature, with
+    /// its fixed arguments followed by any number of extra arguments
+    pub fn apply_vararg(&self, args: &[DynValue]) -> Result<DynValue, ApplyError> {
+        let sig = self.get_signature();
+        if unsafe { jit_type_get_abi(sig.into()) } != Abi::VarArg as jit_abi_t {
+            return Err(ApplyError::NotVariadic)
+        }
+        try!(dynamic::check_args(sig, args, true));
+        let ret_type = try!(sig.get_return().ok_or(ApplyError::NoReturn));
+        let call_sig = dynamic::vararg_signature(sig, args);
+        dynamic::apply_with(ret_type, args, |args, ret| unsafe {
+            jit_function_apply_vararg(self.into(), (&*call_sig).into(), args, ret) != 0
+        })
+    }
+    fn apply_into<'a, R>(&'a self, args: &[&Any], ret: &mut R) -> bool where R: Compile<'a> {
         if cfg!(debug_assertions) {
             let sig = self.get_signature();
             let ret: Option<Type> = sig.get_return().map(|x| x.to_owned());
@@ -117,14 +329,35 @@ impl CompiledFunction {
             assert!(args.len() == num_sig_args, "{:?} expects {} args, but got {}", sig, num_sig_args, args.len());
             assert!(ret.as_ref() == Some(&r), "{:?} returns {:?}, but got {:?}", sig, ret, r);
         }
-        let mut ret: R = R::default();
         unsafe {
             let mut nargs:Vec<_> = args.iter().map(|v| {
                 traitobject::data(v)
             }).collect();
-            jit_function_apply(self.into(), nargs.as_mut_ptr() as *mut *mut c_void, &mut ret as *mut R as *mut c_void);
+            jit_function_apply(self.into(), nargs.as_mut_ptr() as *mut *mut c_void, ret as *mut R as *mut c_void) != 0
         }
-        ret
+    }
+}
+
+/// The try regions `build_try` has made in a function, which share its
+/// catcher block
+struct Catcher {
+    /// The start, end and handler labels of each region, innermost first
+    regions: Vec<(jit_label_t, jit_label_t, jit_label_t)>,
+    /// Whether the catcher block has been started
+    started: bool
+}
+
+extern fn build_on_demand(func: jit_function_t) -> c_int {
+    unsafe {
+        let builder = jit_function_get_meta(func, ON_DEMAND_META);
+        if builder.is_null() {
+            return BuiltinException::CompileError.get_code()
+        }
+        let builder: &Box<Fn(&UncompiledFunction)> = mem::transmute(builder);
+        let func: &UncompiledFunction = from_ptr(func);
+        builder(func);
+        func.finish_catcher();
+        JIT_RESULT_OK
     }
 }
 
@@ -188,13 +421,27 @@ macro_rules! expect(
 /// user calls `function.compile()` to convert it into its executable form.
 pub struct UncompiledFunction(());
 native_ref!(&UncompiledFunction = jit_function_t);
-impl fmt::Debug for UncompiledFunction {
+impl fmt::Display for UncompiledFunction {
+    /// Write the instructions that have been built in this function so far
+    ///
+    /// ```rust
+    /// use jit::*;
+    /// let ctx = Context::<()>::new();
+    /// let func = UncompiledFunction::new(&ctx, &get::<fn(i32) -> i32>());
+    /// func.insn_return(func.insn_add(&func[0], &func[0]));
+    /// assert!(func.to_string().starts_with("function"));
+    /// ```
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         write!(fmt, "{}", try!(util::dump(|fd| unsafe {
             jit_dump_function(fd as *mut c_void, self.into(), ptr::null());
         })))
     }
 }
+impl fmt::Debug for UncompiledFunction {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, fmt)
+    }
+}
 impl DisposeRef for UncompiledFunction {
     type RefTo = Struct__jit_function;
     unsafe fn dispose(p: jit_function_t) {
@@ -227,16 +474,21 @@ impl Index<usize> for UncompiledFunction {
 impl UncompiledFunction {
     #[inline(always)]
     /// Create a new function block and associate it with a JIT context.
-    /// It is recommended that you call `Function::new` and `function.compile()`
-    /// in the closure you give to `context.build(...)`.
+    /// It is recommended that you call `UncompiledFunction::new` and
+    /// `UncompiledFunction::compile` in the closure you give to
+    /// `context.build(...)`.
     ///
     /// This will protect the JIT's internal data structures within a
     /// multi-threaded environment.
     ///
     /// ```rust
     /// use jit::*;
-    /// let mut ctx = Context::<()>::new();
-    /// let func = UncompiledFunction::new(&mut ctx, &get::<fn(f64) -> f64>());
+    /// let ctx = Context::<()>::new();
+    /// ctx.build(|builder| {
+    ///     let func = UncompiledFunction::new(builder, &get::<fn(f64) -> f64>());
+    ///     func.insn_return(&func[0]);
+    ///     UncompiledFunction::compile(func).unwrap();
+    /// });
     /// ```
     pub fn new<'a, T>(context:&'a Context<T>, signature:&Ty) -> CSemiBox<'a, UncompiledFunction> {
         unsafe {
@@ -299,6 +551,15 @@ impl UncompiledFunction {
     pub fn insn_of<'a, T>(&'a self, val:T) -> &'a Val where T:Compile<'a> {
         val.compile(self)
     }
+    /// Make a constant slice of the contents of `vec`, which are kept alive
+    /// for as long as this function is
+    ///
+    /// Vectors don't implement `Compile`, because Rust doesn't give them a
+    /// layout that could be used in signatures.
+    pub fn insn_of_vec<'a, T>(&'a self, vec: Vec<T>) -> &'a Val where T:Compile<'a> + 'static {
+        let contents: &'a [T] = util::keep_alive(self, vec.into_boxed_slice());
+        contents.compile(self)
+    }
     #[inline(always)]
     /// Notify the function building process that this function has a catch block
     /// in it. This must be called before any code that is part of a try block
@@ -315,6 +576,148 @@ impl UncompiledFunction {
         }
     }
     #[inline(always)]
+    /// Make an instruction that gets the exception object that was most recently
+    /// thrown in this function
+    pub fn insn_thrown_exception(&self) -> &Val {
+        unsafe {
+            from_ptr(jit_insn_thrown_exception(self.into()))
+        }
+    }
+    /// Start the catcher block for this function, returning the exception object
+    /// that was thrown.
+    ///
+    /// There can only be one catcher block per function, and it should only
+    /// be entered when an exception is thrown. This panics if the catcher was
+    /// already started, or if `build_try` has been used, because that makes
+    /// the catcher itself when the function is compiled.
+    pub fn insn_start_catcher(&self) -> &Val {
+        let catcher = self.catcher();
+        assert!(!catcher.started && catcher.regions.is_empty(),
+            "The catcher block can only be started once, and not in a function that uses build_try");
+        catcher.started = true;
+        unsafe {
+            from_ptr(jit_insn_start_catcher(self.into()))
+        }
+    }
+    #[inline(always)]
+    /// Make an instruction that branches to `label` if the program counter where
+    /// the exception was thrown is not between the labels `start` and `end`
+    ///
+    /// This is used in the catcher block to work out which `try` region the
+    /// exception came from.
+    pub fn insn_branch_if_pc_not_in_range(&self, start: &Label, end: &Label, label: &mut Label) {
+        unsafe {
+            jit_insn_branch_if_pc_not_in_range(self.into(), **start, **end, &mut **label);
+        }
+    }
+    #[inline(always)]
+    /// Make an instruction that rethrows the current exception to the caller
+    /// because it was not handled by this function's catcher
+    pub fn insn_rethrow_unhandled(&self) {
+        unsafe {
+            jit_insn_rethrow_unhandled(self.into());
+        }
+    }
+    #[inline(always)]
+    /// Start a `finally` clause at the label given
+    pub fn insn_start_finally(&self, label: &mut Label) {
+        unsafe {
+            jit_insn_start_finally(self.into(), &mut **label);
+        }
+    }
+    #[inline(always)]
+    /// Make an instruction that returns from a `finally` clause to the code
+    /// that called it
+    pub fn insn_return_from_finally(&self) {
+        unsafe {
+            jit_insn_return_from_finally(self.into());
+        }
+    }
+    #[inline(always)]
+    /// Make an instruction that calls the `finally` clause at the label given
+    pub fn insn_call_finally(&self, label: &mut Label) {
+        unsafe {
+            jit_insn_call_finally(self.into(), &mut **label);
+        }
+    }
+    #[inline(always)]
+    /// Start a filter clause at the label given, which will be passed a value of
+    /// the type `ty` and returns that value
+    pub fn insn_start_filter(&self, label: &mut Label, ty: &Ty) -> &Val {
+        unsafe {
+            from_ptr(jit_insn_start_filter(self.into(), &mut **label, ty.into()))
+        }
+    }
+    #[inline(always)]
+    /// Make an instruction that returns from a filter clause with the value given
+    pub fn insn_return_from_filter(&self, value: &Val) {
+        unsafe {
+            jit_insn_return_from_filter(self.into(), value.into());
+        }
+    }
+    #[inline(always)]
+    /// Make an instruction that calls the filter clause at the label given with
+    /// the value given, and gets the value it returns as type `ty`
+    pub fn insn_call_filter(&self, label: &mut Label, value: &Val, ty: &Ty) -> &Val {
+        unsafe {
+            from_ptr(jit_insn_call_filter(self.into(), &mut **label, value.into(), ty.into()))
+        }
+    }
+    #[inline(always)]
+    /// Mark the current position in the function with the offset given, which
+    /// is reported by stack traces for the code that follows it
+    pub fn insn_mark_offset(&self, offset: i32) {
+        unsafe {
+            jit_insn_mark_offset(self.into(), offset);
+        }
+    }
+    #[inline(always)]
+    /// Mark a breakpoint at the current position in the function, which will
+    /// stop the thread if it matches a breakpoint added to a `Debugger`
+    ///
+    /// `data1` should be `debugger::LINE`, `debugger::ENTER`, `debugger::LEAVE`
+    /// or `debugger::THROW` for the debugger's `step`, `next` and `finish`
+    /// to work, and `data2` can be whatever the front end wants, like a line number.
+    pub fn insn_mark_breakpoint(&self, data1: jit_nint, data2: jit_nint) {
+        unsafe {
+            jit_insn_mark_breakpoint(self.into(), data1, data2);
+        }
+    }
+    #[inline(always)]
+    /// Mark a breakpoint at the current position in the function, where the
+    /// data is read from the values given when the breakpoint is reached
+    pub fn insn_mark_breakpoint_variable(&self, data1: &Val, data2: &Val) {
+        unsafe {
+            jit_insn_mark_breakpoint_variable(self.into(), data1.into(), data2.into());
+        }
+    }
+    /// Mark the current position in the function with the offset given, and
+    /// record that the code that follows it was generated from `location`
+    ///
+    /// ```rust
+    /// use jit::*;
+    /// let mut ctx = Context::<()>::new();
+    /// let func = UncompiledFunction::new(&mut ctx, &get::<fn() -> i32>());
+    /// func.insn_mark_location(0, SourceLocation::new("main.script", 3, 5));
+    /// func.insn_return(func.insn_of(42i32));
+    /// assert_eq!(func.get_location(0).unwrap().line, 3);
+    /// ```
+    pub fn insn_mark_location(&self, offset: u32, location: SourceLocation) {
+        unsafe {
+            let mut meta = jit_function_get_meta(self.into(), SOURCE_MAP_META);
+            if meta.is_null() {
+                let map = Box::new(SourceMap::new());
+                if jit_function_set_meta(self.into(), SOURCE_MAP_META, mem::transmute(map), Some(::free_data::<SourceMap>), 0) == 0 {
+                    oom()
+                }
+                meta = jit_function_get_meta(self.into(), SOURCE_MAP_META);
+            }
+            let map: &mut SourceMap = mem::transmute(meta);
+            map.insert(offset, location);
+        }
+        self.insn_mark_offset(offset as i32);
+    }
+    #[inline(always)]
     /// Make an instruction that will return from the function with the value given
     pub fn insn_return(&self, retval: &Val) {
         unsafe {
@@ -536,6 +939,115 @@ impl UncompiledFunction {
             jit_insn_store_relative(self.into(), dest.into(), offset as jit_nint, value.into());
         }
     }
+    /// Check that `base` is a pointer to `elem_type` and `index` is an integer
+    fn check_elem(&self, insn: &str, base: &Val, index: &Val, elem_type: &Ty) {
+        let base_type = base.get_type();
+        match base_type.get_ref() {
+            None =>
+                panic!("Base address given to {} should be pointer, got {:?}", insn, base_type),
+            Some(pointee) if pointee.get_kind() != TypeKind::Void && pointee != elem_type =>
+                panic!("Element type given to {} should be {:?}, got {:?}", insn, pointee, elem_type),
+            _ => ()
+        }
+        if !index.get_type().is_int() {
+            panic!("Index given to {} should be integer, got {:?}", insn, index.get_type());
+        }
+    }
+    #[inline(always)]
+    /// Make an instruction that loads the element of type `elem_type` at position `index`
+    /// in the array starting at `base_addr`, which must be a pointer to `elem_type`
+    pub fn insn_load_elem(&self, base_addr: &Val, index: &Val, elem_type: &Ty) -> &Val {
+        if cfg!(debug_assertions) {
+            self.check_elem("insn_load_elem", base_addr, index, elem_type);
+        }
+        unsafe {
+            from_ptr(jit_insn_load_elem(
+                self.into(),
+                base_addr.into(),
+                index.into(),
+                elem_type.into()
+            ))
+        }
+    }
+    #[inline(always)]
+    /// Make an instruction that gets the address of the element of type `elem_type` at
+    /// position `index` in the array starting at `base_addr`, which must be a pointer to
+    /// `elem_type`
+    pub fn insn_load_elem_address(&self, base_addr: &Val, index: &Val, elem_type: &Ty) -> &Val {
+        if cfg!(debug_assertions) {
+            self.check_elem("insn_load_elem_address", base_addr, index, elem_type);
+        }
+        unsafe {
+            from_ptr(jit_insn_load_elem_address(
+                self.into(),
+                base_addr.into(),
+                index.into(),
+                elem_type.into()
+            ))
+        }
+    }
+    #[inline(always)]
+    /// Make an instruction that stores `value` as the element at position `index` in the
+    /// array starting at `base_addr`, which must be a pointer to the type of `value`
+    pub fn insn_store_elem(&self, base_addr: &Val, index: &Val, value: &Val) {
+        if cfg!(debug_assertions) {
+            self.check_elem("insn_store_elem", base_addr, index, value.get_type());
+        }
+        unsafe {
+            jit_insn_store_elem(self.into(), base_addr.into(), index.into(), value.into());
+        }
+    }
+    /// Check that `slice` is a slice, like the values `&[T]` and `Vec<T>` compile to
+    fn check_slice(&self, insn: &str, slice: &Val) {
+        let ty = slice.get_type();
+        let mut fields = ty.fields().map(|field| field.get_type());
+        let is_slice = match (fields.next(), fields.next(), fields.next()) {
+            (Some(ptr), Some(len), None) => ptr.is_pointer() && len.get_kind() == TypeKind::NUInt,
+            _ => false
+        };
+        if !ty.is_struct() || !is_slice {
+            panic!("Value given to {} should be slice, got {:?}", insn, ty);
+        }
+    }
+    #[inline(always)]
+    /// Make an instruction that gets the pointer to the first element of `slice`
+    pub fn insn_slice_ptr(&self, slice: &Val) -> &Val {
+        if cfg!(debug_assertions) {
+            self.check_slice("insn_slice_ptr", slice);
+        }
+        let ptr = slice.get_type().fields().next().unwrap();
+        self.insn_load_relative(slice, ptr.get_offset(), ptr.get_type())
+    }
+    #[inline(always)]
+    /// Make an instruction that gets the number of elements in `slice`
+    pub fn insn_slice_len(&self, slice: &Val) -> &Val {
+        if cfg!(debug_assertions) {
+            self.check_slice("insn_slice_len", slice);
+        }
+        let len = slice.get_type().fields().nth(1).unwrap();
+        self.insn_load_relative(slice, len.get_offset(), len.get_type())
+    }
+    /// Make instructions that load the element at position `index` in `slice`,
+    /// throwing the builtin `OutOfBounds` exception if `index` is not below its length
+    pub fn insn_load_elem_checked(&self, slice: &Val, index: &Val) -> &Val {
+        let len = self.insn_slice_len(slice);
+        let index = self.insn_convert(index, &::get::<usize>(), false);
+        let mut in_bounds = Label::new(self);
+        self.insn_branch_if(self.insn_lt(index, len), &mut in_bounds);
+        unsafe {
+            let code = self.insn_of(BuiltinException::OutOfBounds.get_code());
+            self.insn_call_native(
+                Some("jit_exception_builtin"),
+                jit_exception_builtin as *mut (),
+                &::get::<extern fn(c_int)>(),
+                &[code],
+                CallFlags::NO_RETURN
+            );
+        }
+        self.insn_label(&mut in_bounds);
+        let ptr = self.insn_slice_ptr(slice);
+        self.insn_load_elem(ptr, index, ptr.get_type().get_ref().unwrap())
+    }
     #[inline(always)]
     /// Make an instruction that sets a label
     pub fn insn_label(&self, label: &mut Label) {
@@ -766,6 +1278,43 @@ impl UncompiledFunction {
             ))
         }
     }
+    /// Make an instruction that calls a function that has the signature given
+    /// with some arguments through a pointer from a vtable, which should have
+    /// been made with `Func::to_vtable_pointer`
+    pub fn insn_call_indirect_vtable(&self, func: &Val, signature: &Ty,
+                               args: &[&Val], flags: CallFlags) -> &Val {
+        if cfg!(debug_assertions) {
+            if !signature.is_signature() {
+                panic!("Signature given to insn_call_indirect_vtable should be signature, got {:?}", signature);
+            }
+            let func_type = func.get_type();
+            if !func_type.is_pointer() && !func_type.is_signature() {
+                panic!("Vtable pointer given to insn_call_indirect_vtable should be pointer, got {:?}", func_type);
+            }
+            let num_params = signature.params().count();
+            if args.len() != num_params {
+                panic!("{:?} takes {} arguments, but got {}", signature, num_params, args.len());
+            }
+            for (index, (arg, param)) in args.iter().zip(signature.params()).enumerate() {
+                let ty = arg.get_type();
+                if ty != param {
+                    panic!("argument #{} to {:?} should be {:?}, but got {:?}", index, signature, param, ty);
+                }
+            }
+        }
+        unsafe {
+            let native_args: &[jit_value_t] = mem::transmute(args);
+            let mut native_args: Vec<jit_value_t> = native_args.to_owned();
+            from_ptr(jit_insn_call_indirect_vtable(
+                self.into(),
+                func.into(),
+                signature.into(),
+                native_args.as_mut_ptr(),
+                native_args.len() as c_uint,
+                flags.bits()
+            ))
+        }
+    }
     /// Make an instruction that calls a native function that has the signature
     /// given with some arguments
     pub unsafe fn insn_call_native(&self, name: Option<&str>,
@@ -948,6 +1497,127 @@ impl UncompiledFunction {
             each();
         })
     }
+    /// Make instructions to run the block `body`, then run `handler` with the
+    /// exception object if anything in `body` throws. `finally` is run after
+    /// either of them, and before any exception that escapes is rethrown,
+    /// including one thrown by `handler`.
+    ///
+    /// This can be used any number of times in a function, and nested inside
+    /// `body` or `handler`, where the innermost `try` catches an exception
+    /// first. The regions share the function's catcher block, which is made
+    /// when the function is compiled, so `insn_start_catcher` can't be used
+    /// in the same function.
+    ///
+    /// ```rust
+    /// use jit::*;
+    /// let mut ctx = Context::<()>::new();
+    /// let func = UncompiledFunction::new(&mut ctx, &get::<fn(i32) -> i32>());
+    /// let x = &func[0];
+    /// func.build_try(|| {
+    ///     func.build_if(func.insn_eq(x, func.insn_of(0i32)), || {
+    ///         func.insn_throw(func.insn_of(1usize as *const u8));
+    ///     });
+    ///     func.insn_return(x);
+    /// }, |_| {
+    ///     func.insn_return(func.insn_of(-1i32));
+    /// }, || ());
+    /// ```
+    pub fn build_try<'a, B, H, F>(&'a self, body: B, handler: H, finally: F)
+        where B: FnOnce(), H: FnOnce(&'a Val), F: FnOnce() {
+        assert!(!self.catcher().started, "build_try can't be used once the catcher block has been started");
+        self.insn_uses_catcher();
+        // Each region gets its own variable, so a `try` nested in the handler
+        // can't overwrite the exception this handler was given
+        let exception: &'a Val = Val::new(self, consts::get_void_ptr());
+        let mut start = Label::new(self);
+        let mut end = Label::new(self);
+        let mut handler_start = Label::new(self);
+        let mut handler_end = Label::new(self);
+        let mut rethrow = Label::new(self);
+        let mut finally_label = Label::new(self);
+        let mut after = Label::new(self);
+        self.insn_label(&mut start);
+        body();
+        self.insn_label(&mut end);
+        self.insn_call_finally(&mut finally_label);
+        self.insn_branch(&mut after);
+        self.insn_label(&mut handler_start);
+        self.insn_store(exception, self.insn_last_exception());
+        handler(exception);
+        self.insn_label(&mut handler_end);
+        self.insn_call_finally(&mut finally_label);
+        self.insn_branch(&mut after);
+        self.insn_label(&mut rethrow);
+        self.insn_call_finally(&mut finally_label);
+        self.insn_throw(self.insn_last_exception());
+        self.insn_start_finally(&mut finally_label);
+        finally();
+        self.insn_return_from_finally();
+        self.insn_label(&mut after);
+        // Any regions nested in this one were pushed while `body` and
+        // `handler` were built, so they come first and catch first
+        let regions = &mut self.catcher().regions;
+        regions.push((*start, *end, *handler_start));
+        regions.push((*handler_start, *handler_end, *rethrow));
+    }
+    /// Make an instruction that gets the exception that was last thrown in
+    /// this thread
+    fn insn_last_exception(&self) -> &Val {
+        unsafe {
+            self.insn_call_native(
+                Some("jit_exception_get_last"),
+                jit_exception_get_last as *mut (),
+                &::get::<extern fn() -> *mut c_void>(),
+                &[],
+                CallFlags::NO_THROW
+            )
+        }
+    }
+    /// Get the try regions of this function and whether its catcher block has
+    /// been started, which are kept until it is compiled
+    fn catcher(&self) -> &mut Catcher {
+        unsafe {
+            let mut meta = jit_function_get_meta(self.into(), CATCHER_META);
+            if meta.is_null() {
+                let catcher = Box::new(Catcher {
+                    regions: Vec::new(),
+                    started: false
+                });
+                if jit_function_set_meta(self.into(), CATCHER_META, mem::transmute(catcher), Some(::free_data::<Catcher>), 1) == 0 {
+                    oom()
+                }
+                meta = jit_function_get_meta(self.into(), CATCHER_META);
+            }
+            &mut *(meta as *mut Catcher)
+        }
+    }
+    /// Make the catcher block for the regions made by `build_try`, which
+    /// branches to the handler of the innermost region the exception was
+    /// thrown in
+    ///
+    /// This is done just before the function is compiled, once every region
+    /// is known.
+    fn finish_catcher(&self) {
+        unsafe {
+            let func = self.into();
+            if jit_function_get_meta(func, CATCHER_META).is_null() {
+                return
+            }
+            let catcher = self.catcher();
+            if catcher.started || catcher.regions.is_empty() {
+                return
+            }
+            catcher.started = true;
+            jit_insn_start_catcher(func);
+            for &(start, end, mut handler) in &catcher.regions {
+                let mut next = jit_function_reserve_label(func);
+                jit_insn_branch_if_pc_not_in_range(func, start, end, &mut next);
+                jit_insn_branch(func, &mut handler);
+                jit_insn_label(func, &mut next);
+            }
+            jit_insn_rethrow_unhandled(func);
+        }
+    }
     #[inline(always)]
     /// Set the optimization level of the function, where the bigger the level,
     /// the more effort should be spent optimising
@@ -965,11 +1635,37 @@ impl UncompiledFunction {
     }
     #[inline(always)]
     /// Make this function a candidate for recompilation
+    ///
+    /// This must be called before the function is first compiled. Calls to a
+    /// recompilable function go through a trampoline, so closures made from it
+    /// before it is recompiled will call the new code afterwards.
     pub fn set_recompilable(&self) {
         unsafe {
             jit_function_set_recompilable(self.into());
         }
     }
+    #[inline(always)]
+    /// Stop this function from being a candidate for recompilation
+    pub fn clear_recompilable(&self) {
+        unsafe {
+            jit_function_clear_recompilable(self.into());
+        }
+    }
+    /// Compile the function without replacing the code that calls to it
+    /// currently run, and return the entry point of the new code
+    ///
+    /// Use `Func::setup_entry` to make calls run the new code.
+    pub fn compile_entry(&self) -> Option<*mut c_void> {
+        self.finish_catcher();
+        unsafe {
+            let mut entry = ptr::null_mut();
+            if jit_function_compile_entry(self.into(), &mut entry) == 0 {
+                None
+            } else {
+                Some(entry)
+            }
+        }
+    }
     /// Get the entry block of this function
     pub fn get_entry(&self) -> Option<&Block> {
         unsafe {
@@ -982,14 +1678,103 @@ impl UncompiledFunction {
             from_ptr_opt(jit_function_get_current(self.into()) as jit_block_t)
         }
     }
-    #[inline(always)]
+    /// Iterate through the blocks of this function, starting with the entry
+    pub fn blocks(&self) -> Blocks {
+        Blocks::new(self)
+    }
+    /// Check this function for ill-typed instructions, branches to labels
+    /// that are never placed, and paths that reach the end of the function
+    /// without returning a value
+    ///
+    /// ```rust
+    /// use jit::*;
+    /// let ctx = Context::<()>::new();
+    /// let func = UncompiledFunction::new(&ctx, &get::<fn(i32) -> i32>());
+    /// assert!(func.verify().is_err());
+    /// func.insn_return(&func[0]);
+    /// assert!(func.verify().is_ok());
+    /// ```
+    pub fn verify(&self) -> Result<(), Vec<VerifyError>> {
+        verify::verify(self)
+    }
+    /// Verify the function, then compile it if no mistakes were found
+    ///
+    /// If mistakes were found, the function is abandoned.
+    pub fn compile_checked<'a>(func: CSemiBox<'a, UncompiledFunction>) -> Result<CSemiBox<'a, CompiledFunction>, CompileError> {
+        func.finish_catcher();
+        try!(func.verify().map_err(CompileError::Invalid));
+        UncompiledFunction::compile(func)
+    }
     /// Compile the function
-    pub fn compile<'a>(func: CSemiBox<'a, UncompiledFunction>) -> CSemiBox<'a, CompiledFunction> {
+    ///
+    /// If LibJIT fails to compile it, the function is abandoned so nothing is
+    /// left half-built in its context.
+    ///
+    /// ```rust
+    /// use jit::*;
+    /// let ctx = Context::<()>::new();
+    /// let func = UncompiledFunction::new(&ctx, &get::<fn(i32) -> i32>());
+    /// func.insn_return(func.insn_neg(&func[0]));
+    /// let func = UncompiledFunction::compile(func).unwrap();
+    /// let negate: extern fn(i32) -> i32 = func.as_func();
+    /// assert_eq!(negate(3), -3);
+    /// ```
+    pub fn compile<'a>(func: CSemiBox<'a, UncompiledFunction>) -> Result<CSemiBox<'a, CompiledFunction>, CompileError> {
+        func.finish_catcher();
         unsafe {
             let ptr = (&*func).into();
             mem::forget(func);
-            jit_function_compile(ptr);
-            CSemiBox::new(ptr)
+            if jit_function_compile(ptr) == 0 {
+                jit_function_abandon(ptr);
+                Err(CompileError::Failed)
+            } else {
+                Ok(CSemiBox::new(ptr))
+            }
+        }
+    }
+    /// Give up on building the function, which destroys it and detaches it
+    /// from its context
+    ///
+    /// This is what happens when an `UncompiledFunction` is dropped, but it
+    /// makes it explicit when a build is abandoned after an error.
+    pub fn abandon(func: CSemiBox<UncompiledFunction>) {
+        mem::drop(func)
+    }
+}
+
+/// The reason a function couldn't be compiled
+#[derive(Clone, PartialEq)]
+pub enum CompileError {
+    /// The function failed verification, for the reasons given
+    Invalid(Vec<VerifyError>),
+    /// LibJIT failed to compile the function, usually because it ran out of
+    /// memory for the code
+    Failed
+}
+impl fmt::Display for CompileError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CompileError::Invalid(ref errors) => {
+                try!(write!(fmt, "{}:", self.description()));
+                for error in errors {
+                    try!(write!(fmt, "\n    {}", error));
+                }
+                Ok(())
+            },
+            CompileError::Failed => write!(fmt, "{}", self.description())
+        }
+    }
+}
+impl fmt::Debug for CompileError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, fmt)
+    }
+}
+impl Error for CompileError {
+    fn description(&self) -> &str {
+        match *self {
+            CompileError::Invalid(_) => "Function failed verification",
+            CompileError::Failed => "Function failed to compile"
         }
     }
 }
@@ -2,11 +2,13 @@ use raw::*;
 use context::{Context, ContextMember};
 use function::{UncompiledFunction, FunctionMember};
 use types::Ty;
-use label::Label;
-use util::{from_ptr, from_ptr_opt};
+use label::{self, Label};
+use opcode::{Opcode, JIT_OPCODE_IS_BRANCH};
+use util::{self, from_ptr, from_ptr_opt};
 use value::Val;
-use std::{ffi, fmt, mem, str};
+use std::{ffi, fmt, mem, ptr, str};
 use std::marker::PhantomData;
+use std::os::raw::c_void;
 
 /// Represents a single LibJIT instruction
 pub struct Instruction(PhantomData<[()]>);
@@ -27,8 +29,13 @@ impl FunctionMember for Instruction {
 	}
 }
 impl Instruction {
-	/// Get the opcode of the instruction
-	pub fn get_opcode(&self) -> i32 {
+	/// Get the opcode of the instruction, or `None` if LibJIT has an opcode
+	/// that `Opcode` doesn't know about
+	pub fn get_opcode(&self) -> Option<Opcode> {
+		Opcode::from_raw(self.get_raw_opcode())
+	}
+	/// Get the number LibJIT uses for the opcode of the instruction
+	pub fn get_raw_opcode(&self) -> i32 {
 		unsafe {
 			jit_insn_get_opcode(self.into())
 		}
@@ -77,6 +84,37 @@ impl Instruction {
 			str::from_utf8(c_name.to_bytes()).unwrap()
 		}
 	}
+	/// Check if this instruction branches to a label
+	pub fn is_branch(&self) -> bool {
+		let opcode = self.get_raw_opcode();
+		unsafe {
+			opcode >= 0 && (opcode as usize) < jit_opcodes.len()
+				&& jit_opcodes[opcode as usize].flags & JIT_OPCODE_IS_BRANCH != 0
+		}
+	}
+	/// Get the label this instruction branches to, if it is a branch
+	pub fn get_label(&self) -> Option<Label> {
+		if self.is_branch() {
+			unsafe {
+				label::from_raw(jit_insn_get_label(self.into()))
+			}
+		} else {
+			None
+		}
+	}
+	/// Get the block in `func` this instruction branches to, if it is a branch
+	pub fn get_target<'a>(&self, func: &'a UncompiledFunction) -> Option<&'a Block> {
+		self.get_label().and_then(|label| unsafe {
+			from_ptr_opt(jit_block_from_label(func.into(), *label))
+		})
+	}
+	/// Dump this instruction as it would appear in the dump of `func`, which
+	/// should be the function that contains it
+	pub fn dump(&self, func: &UncompiledFunction) -> String {
+		util::dump(|fd| unsafe {
+			jit_dump_insn(fd as *mut c_void, func.into(), mem::transmute::<jit_insn_t, jit_value_t>(self.into()));
+		}).unwrap_or_else(|_| String::new())
+	}
 }
 impl fmt::Debug for Instruction {
 	fn fmt(&self, fmt:&mut fmt::Formatter) -> fmt::Result {
@@ -85,7 +123,7 @@ impl fmt::Debug for Instruction {
 		write!(fmt, "{}({}, {})", self.get_name(), v1, v2)
 	}
 }
-
+/// An iterator through the instructions of a block, from first to last
 pub struct InstructionIter<'a> {
 	_iter: jit_insn_iter_t,
 	marker: PhantomData<&'a ()>,
@@ -99,10 +137,47 @@ impl<'a> Iterator for InstructionIter<'a> {
 		}
 	}
 }
+/// An iterator through the instructions of a block, from last to first
+pub struct RevInstructionIter<'a> {
+	_iter: jit_insn_iter_t,
+	marker: PhantomData<&'a ()>,
+}
+impl<'a> Iterator for RevInstructionIter<'a> {
+	type Item = &'a Instruction;
+	fn next(&mut self) -> Option<&'a Instruction> {
+		unsafe {
+			let ptr = jit_insn_iter_previous(&mut self._iter);
+			from_ptr_opt(ptr as jit_insn_t)
+		}
+	}
+}
+/// An iterator through the blocks of a function, in the order they were made
+pub struct Blocks<'a> {
+	func: &'a UncompiledFunction,
+	block: jit_block_t
+}
+impl<'a> Blocks<'a> {
+	/// Iterate through the blocks of `func`
+	pub fn new(func: &'a UncompiledFunction) -> Blocks<'a> {
+		Blocks {
+			func: func,
+			block: ptr::null_mut()
+		}
+	}
+}
+impl<'a> Iterator for Blocks<'a> {
+	type Item = &'a Block;
+	fn next(&mut self) -> Option<&'a Block> {
+		unsafe {
+			self.block = jit_block_next(self.func.into(), self.block);
+			from_ptr_opt(self.block)
+		}
+	}
+}
 
 /// Represents a single LibJIT block
 pub struct Block(PhantomData<[()]>);
-native_ref!(&Block = jit_block_t);
+native_ref!(comparable &Block = jit_block_t);
 impl ContextMember for Block {
 	fn get_context(&self) -> &Context {
 		self.get_function().get_context()
@@ -128,6 +203,53 @@ impl Block {
 			from_ptr(jit_block_get_function(self.into()))
 		}
 	}
+	/// Get the first label that starts this block
+	pub fn get_label(&self) -> Option<Label> {
+		unsafe {
+			label::from_raw(jit_block_get_label(self.into()))
+		}
+	}
+	/// Get the block that comes after this one in its function
+	pub fn next(&self) -> Option<&Block> {
+		unsafe {
+			from_ptr_opt(jit_block_next(self.get_function().into(), self.into()))
+		}
+	}
+	/// Get the block that comes before this one in its function
+	pub fn previous(&self) -> Option<&Block> {
+		unsafe {
+			from_ptr_opt(jit_block_previous(self.get_function().into(), self.into()))
+		}
+	}
+	/// Get the blocks that control can flow to from the end of this block
+	///
+	/// These are the targets of any branches in this block, followed by the
+	/// next block if control can fall through into it.
+	pub fn successors(&self) -> Vec<&Block> {
+		let func = self.get_function();
+		let mut blocks: Vec<&Block> = Vec::new();
+		for insn in self.iter() {
+			if let Some(target) = insn.get_target(func) {
+				if !blocks.contains(&target) {
+					blocks.push(target);
+				}
+			}
+		}
+		if !self.ends_in_dead() {
+			if let Some(next) = self.next() {
+				if !blocks.contains(&next) {
+					blocks.push(next);
+				}
+			}
+		}
+		blocks
+	}
+	/// Get the blocks that control can flow to this block from
+	pub fn predecessors(&self) -> Vec<&Block> {
+		Blocks::new(self.get_function())
+			.filter(|block| block.successors().contains(&self))
+			.collect()
+	}
 	/// Check if the block is reachable
 	pub fn is_reachable(&self) -> bool {
 		unsafe {
@@ -153,4 +275,15 @@ impl Block {
 			}
 		}
 	}
+	/// Iterate through the instructions backwards, from last to first
+	pub fn iter_rev(&self) -> RevInstructionIter {
+		unsafe {
+			let mut iter = mem::zeroed();
+			jit_insn_iter_init_last(&mut iter, self.into());
+			RevInstructionIter {
+				_iter: iter,
+				marker: PhantomData
+			}
+		}
+	}
 }
@@ -55,16 +55,26 @@ use raw::*;
 use std::os::raw::c_void;
 use std::mem;
 pub use compile::Compile;
-pub use context::{Context, ContextMember};
+pub use context::{Builder, Context, ContextMember, SyncContext};
+pub use debugger::{Debugger, DebuggerEvent};
+pub use dynamic::{call_native, AlignedBytes, ApplyError, DynValue};
 pub use elf::*;
-pub use function::{flags, Abi, UncompiledFunction, Func, CompiledFunction};
+pub use exception::JitException;
+pub use function::{flags, Abi, CompileError, UncompiledFunction, Func, CompiledFunction};
 pub use function::flags::CallFlags;
 pub use label::Label;
-pub use insn::{Block, Instruction, InstructionIter};
+pub use memory::{CodeLimit, MemoryManager, MemoryStats};
+pub use queue::{CompileFuture, CompileQueue, Compiled, QueueError};
+pub use opcode::{Opcode, OperandKind, Operands};
+pub use insn::{Block, Blocks, Instruction, InstructionIter, RevInstructionIter};
+pub use source::{SourceLocation, SourceMap};
+pub use tiered::TieredFunction;
+pub use trace::{StackTrace, StackFrame, StackFrames, Unwinder};
 pub use types::TypeKind;
 pub use types::{get, Type, Field, Fields, Params, CowType, StaticType, Ty, TaggedType};
 pub use types::consts as typecs;
 pub use value::Val;
+pub use verify::VerifyError;
 
 
 extern fn free_data<T>(data: *mut c_void) {
@@ -102,17 +112,35 @@ pub fn supports_virtual_memory() -> bool {
         jit_supports_virtual_memory() != 0
     }
 }
+/// Check if the JIT supports closures
+#[inline]
+pub fn supports_closures() -> bool {
+    unsafe {
+        jit_supports_closures() != 0
+    }
+}
 #[macro_use]
 mod macros;
+pub mod closure;
 mod context;
 mod compile;
+pub mod debugger;
+mod dynamic;
 mod elf;
+pub mod exception;
 mod function;
 mod insn;
 mod label;
+mod memory;
+mod opcode;
+mod queue;
+mod source;
+mod tiered;
+mod trace;
 mod types;
 mod util;
 mod value;
+mod verify;
 
 
 #[macro_export]
@@ -228,7 +256,7 @@ macro_rules! jit_func(
             let $name = &func;
             $($st;)+
         };
-        let func = UncompiledFunction::compile(func);
+        let func = UncompiledFunction::compile(func).unwrap();
         let $name: extern fn(()) -> $ret = func.as_func(); 
         let $name: extern fn() -> $ret = unsafe { ::std::mem::transmute($name) };
         $value
@@ -245,7 +273,7 @@ macro_rules! jit_func(
             };)*
             $($st;)+
         };
-        let func = UncompiledFunction::compile(func);
+        let func = UncompiledFunction::compile(func).unwrap();
         let $name: extern fn(($($ty),+)) -> $ret = func.as_func();
         let $name: extern fn($($ty),+) -> $ret = unsafe { ::std::mem::transmute($name) };
         $value
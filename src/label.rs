@@ -6,6 +6,21 @@ use function::UncompiledFunction;
 use std::marker::PhantomData;
 use std::fmt;
 use std::ops::{Deref, DerefMut};
+/// The label LibJIT uses to mean that no label has been set
+const UNDEFINED: jit_label_t = !0u32 as jit_label_t;
+
+/// Wrap a label that LibJIT gave back, or return `None` if it is undefined
+pub fn from_raw<'a>(label: jit_label_t) -> Option<Label<'a>> {
+    if label == UNDEFINED {
+        None
+    } else {
+        Some(Label {
+            _label: label,
+            marker: PhantomData
+        })
+    }
+}
+
 #[derive(PartialEq)]
 /// A label in the code that can be branched to in instructions
 pub struct Label<'a> {
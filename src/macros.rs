@@ -88,6 +88,34 @@ macro_rules! compile_tuple(
     )
 );
 
+macro_rules! compile_arrays(
+    ($($len:expr),+) => ($(
+        impl<'a, T> Compile<'a> for [T; $len] where T:Compile<'a> + Copy {
+            #[inline(always)]
+            fn compile(self, func:&'a UncompiledFunction) -> &'a Val {
+                use std::mem;
+                let ty = get::<[T; $len]>();
+                let array = Val::new(func, &ty);
+                for (index, elem) in self.iter().enumerate() {
+                    func.insn_store_relative(array, index * mem::size_of::<T>(), func.insn_of(*elem));
+                }
+                array
+            }
+            #[inline(always)]
+            fn get_type() -> CowType<'a> {
+                use std::mem;
+                let elem = get::<T>();
+                let mut types = vec![&*elem; $len];
+                let ty = Type::new_struct(&mut types);
+                unsafe {
+                    jit_type_set_size_and_alignment((&ty).into(), mem::size_of::<Self>() as i64, mem::align_of::<Self>() as i64);
+                }
+                ty.into()
+            }
+        }
+    )+)
+);
+
 macro_rules! compile_prims(
     ($(($ty:ty, $cast: ty) => ($type_name:ident, $make_constant:ident)),+) => (
         $(compile_prim!($ty, $type_name, $make_constant, $cast);)+
@@ -253,3 +281,22 @@ macro_rules! builtin_types(
         $(builtin_type!($c_name -> $rust_name);)+
     )
 );
+macro_rules! opcodes(
+    ($($variant:ident => $name:expr),*) => (
+        /// An instruction opcode, as listed in LibJIT's `jit-opcode.h`
+        #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+        pub enum Opcode {
+            $($variant),*
+        }
+        /// Every opcode, in the order LibJIT lists them
+        static ALL: &'static [Opcode] = &[$(Opcode::$variant),*];
+        impl Opcode {
+            /// Get the name LibJIT gives this opcode
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Opcode::$variant => $name),*
+                }
+            }
+        }
+    )
+);
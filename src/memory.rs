@@ -0,0 +1,329 @@
+//! Limiting the code LibJIT keeps and choosing where its data goes
+//!
+//! A `MemoryManager` wraps LibJIT's default memory manager. The manager gets
+//! to decide whether each function's code may be kept and how far the code
+//! buffer may grow, and it can allocate the data that code refers to itself.
+//!
+//! Custom arenas for code are not supported. LibJIT's default manager still
+//! allocates the executable memory and lays out the code in it, since its
+//! function lookup and unwinding depend on that layout, so only limits and
+//! data allocation can be changed.
+use raw::*;
+use function::Func;
+use util::{MEMORY_MANAGER_META, from_ptr, oom};
+use std::collections::HashMap;
+use std::os::raw::{c_int, c_void};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::{mem, ptr};
+
+const JIT_MEMORY_OK: c_int = 0;
+const JIT_MEMORY_TOO_BIG: c_int = 2;
+const JIT_MEMORY_ERROR: c_int = 3;
+
+/// Decides how much code a context can keep, and where its data goes
+///
+/// Code always lives in LibJIT's own executable memory, and only data can be
+/// allocated by the manager.
+pub trait MemoryManager: 'static {
+    /// Decide if LibJIT may start writing the code for `func`
+    fn start_function(&mut self, _func: &Func) -> bool {
+        true
+    }
+    /// Decide if the `size` bytes of code LibJIT wrote for `func` may be kept
+    ///
+    /// `size` is how far the default manager's code buffer grew while `func`
+    /// was compiled, so it includes any padding and inline constants.
+    ///
+    /// If this returns `false`, the code is thrown away and compiling `func`
+    /// fails.
+    fn end_function(&mut self, _func: &Func, _size: usize) -> bool {
+        true
+    }
+    /// Called when the `size` bytes of code kept for `func` are freed
+    fn free_function(&mut self, _func: &Func, _size: usize) {
+    }
+    /// Decide if LibJIT may grow its code buffer by `factor` times after a
+    /// function didn't fit in it, which it doubles every time this happens
+    fn extend_limit(&mut self, _factor: usize) -> bool {
+        true
+    }
+    /// Allocate `size` bytes of data aligned to `align` bytes, such as the
+    /// constants that code refers to, or return `None` to let LibJIT
+    /// allocate it
+    ///
+    /// The data must live as long as the context.
+    fn alloc_data(&mut self, _size: usize, _align: usize) -> Option<*mut c_void> {
+        None
+    }
+}
+
+/// Statistics about the code kept by a `CodeLimit`
+#[derive(Default)]
+pub struct MemoryStats {
+    code: AtomicUsize,
+    peak: AtomicUsize,
+    rejected: AtomicUsize,
+    functions: Mutex<HashMap<usize, usize>>
+}
+impl MemoryStats {
+    /// Get the total number of bytes of code currently kept
+    pub fn code_size(&self) -> usize {
+        self.code.load(Ordering::SeqCst)
+    }
+    /// Get the most bytes of code that have been kept at once
+    pub fn peak_code_size(&self) -> usize {
+        self.peak.load(Ordering::SeqCst)
+    }
+    /// Get the number of functions whose code was thrown away because it
+    /// would have gone over the limit
+    pub fn rejected(&self) -> usize {
+        self.rejected.load(Ordering::SeqCst)
+    }
+    /// Get the number of functions whose code is currently kept
+    pub fn functions(&self) -> usize {
+        self.functions.lock().unwrap().len()
+    }
+    /// Get the number of bytes of code kept for `func`, if it has been compiled
+    pub fn function_code_size(&self, func: &Func) -> Option<usize> {
+        let key = func as *const Func as usize;
+        self.functions.lock().unwrap().get(&key).cloned()
+    }
+}
+
+/// A memory manager that puts a hard cap on the total size of the code in a
+/// context, and records how much each function uses
+///
+/// ```rust
+/// use jit::*;
+/// let limit = CodeLimit::new(1 << 20);
+/// let stats = limit.stats();
+/// let ctx = Context::<()>::with_memory_manager(limit);
+/// let func = UncompiledFunction::new(&ctx, &get::<fn(i32) -> i32>());
+/// func.insn_return(func.insn_add(&func[0], &func[0]));
+/// let func = UncompiledFunction::compile(func).unwrap();
+/// assert!(stats.function_code_size(&func).unwrap() > 0);
+/// assert_eq!(stats.code_size(), stats.function_code_size(&func).unwrap());
+/// ```
+pub struct CodeLimit {
+    limit: usize,
+    stats: Arc<MemoryStats>
+}
+impl CodeLimit {
+    /// Make a memory manager that allows at most `limit` bytes of code
+    pub fn new(limit: usize) -> CodeLimit {
+        CodeLimit {
+            limit: limit,
+            stats: Arc::new(MemoryStats::default())
+        }
+    }
+    /// Get the statistics this records, which stay up to date as functions
+    /// are compiled and freed
+    pub fn stats(&self) -> Arc<MemoryStats> {
+        self.stats.clone()
+    }
+}
+impl MemoryManager for CodeLimit {
+    fn end_function(&mut self, func: &Func, size: usize) -> bool {
+        let code = self.stats.code.fetch_add(size, Ordering::SeqCst) + size;
+        if code > self.limit {
+            self.stats.code.fetch_sub(size, Ordering::SeqCst);
+            self.stats.rejected.fetch_add(1, Ordering::SeqCst);
+            return false
+        }
+        let mut peak = self.stats.peak.load(Ordering::SeqCst);
+        while code > peak {
+            match self.stats.peak.compare_exchange(peak, code, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => break,
+                Err(current) => peak = current
+            }
+        }
+        self.stats.functions.lock().unwrap().insert(func as *const Func as usize, size);
+        true
+    }
+    fn free_function(&mut self, func: &Func, size: usize) {
+        self.stats.code.fetch_sub(size, Ordering::SeqCst);
+        self.stats.functions.lock().unwrap().remove(&(func as *const Func as usize));
+    }
+}
+
+/// The memory context LibJIT is given, which wraps the default one
+struct Managed {
+    default: &'static Struct_jit_memory_manager,
+    inner: jit_memory_context_t,
+    manager: Box<MemoryManager>,
+    current: jit_function_t,
+    start: usize,
+    sizes: HashMap<usize, usize>
+}
+
+/// Set `manager` as the memory manager of `ctx`, which must not have had
+/// any functions made in it yet
+pub unsafe fn set_memory_manager<M>(ctx: jit_context_t, manager: M) where M: MemoryManager {
+    let manager: Box<Option<Box<MemoryManager>>> = Box::new(Some(Box::new(manager)));
+    if jit_context_set_meta(ctx, MEMORY_MANAGER_META, mem::transmute(manager), Some(::free_data::<Option<Box<MemoryManager>>>)) == 0 {
+        oom()
+    }
+    jit_context_set_memory_manager(ctx, &MANAGER);
+}
+
+macro_rules! default(
+    ($managed:expr, $name:ident($($arg:expr),*)) => (
+        ($managed.default.$name.unwrap())($managed.inner $(, $arg)*)
+    )
+);
+
+unsafe fn managed<'a>(memctx: jit_memory_context_t) -> &'a mut Managed {
+    &mut *(memctx as *mut Managed)
+}
+
+extern fn create(ctx: jit_context_t) -> jit_memory_context_t {
+    unsafe {
+        let manager = jit_context_get_meta(ctx, MEMORY_MANAGER_META) as *mut Option<Box<MemoryManager>>;
+        let manager = match manager.as_mut().and_then(Option::take) {
+            Some(manager) => manager,
+            None => return ptr::null_mut()
+        };
+        let default = &*jit_default_memory_manager();
+        let inner = (default.create.unwrap())(ctx);
+        if inner.is_null() {
+            return ptr::null_mut()
+        }
+        Box::into_raw(Box::new(Managed {
+            default: default,
+            inner: inner,
+            manager: manager,
+            current: ptr::null_mut(),
+            start: 0,
+            sizes: HashMap::new()
+        })) as jit_memory_context_t
+    }
+}
+extern fn destroy(memctx: jit_memory_context_t) {
+    unsafe {
+        let managed: Box<Managed> = Box::from_raw(memctx as *mut Managed);
+        default!(managed, destroy());
+    }
+}
+extern fn find_function_info(memctx: jit_memory_context_t, pc: *mut c_void) -> jit_function_info_t {
+    unsafe { default!(managed(memctx), find_function_info(pc)) }
+}
+extern fn get_function(memctx: jit_memory_context_t, info: jit_function_info_t) -> jit_function_t {
+    unsafe { default!(managed(memctx), get_function(info)) }
+}
+extern fn get_function_start(memctx: jit_memory_context_t, info: jit_function_info_t) -> *mut c_void {
+    unsafe { default!(managed(memctx), get_function_start(info)) }
+}
+extern fn get_function_end(memctx: jit_memory_context_t, info: jit_function_info_t) -> *mut c_void {
+    unsafe { default!(managed(memctx), get_function_end(info)) }
+}
+extern fn alloc_function(memctx: jit_memory_context_t) -> jit_function_t {
+    unsafe { default!(managed(memctx), alloc_function()) }
+}
+extern fn free_function(memctx: jit_memory_context_t, func: jit_function_t) {
+    unsafe {
+        let managed = managed(memctx);
+        if let Some(size) = managed.sizes.remove(&(func as usize)) {
+            managed.manager.free_function(from_ptr(func), size);
+        }
+        default!(managed, free_function(func))
+    }
+}
+extern fn start_function(memctx: jit_memory_context_t, func: jit_function_t) -> c_int {
+    unsafe {
+        let managed = managed(memctx);
+        let result = default!(managed, start_function(func));
+        if result != JIT_MEMORY_OK {
+            return result
+        }
+        if !managed.manager.start_function(from_ptr(func)) {
+            default!(managed, end_function(JIT_MEMORY_ERROR));
+            return JIT_MEMORY_ERROR
+        }
+        managed.current = func;
+        managed.start = default!(managed, get_break()) as usize;
+        result
+    }
+}
+extern fn end_function(memctx: jit_memory_context_t, result: c_int) -> c_int {
+    unsafe {
+        let managed = managed(memctx);
+        let func = mem::replace(&mut managed.current, ptr::null_mut());
+        if result != JIT_MEMORY_OK || func.is_null() {
+            return default!(managed, end_function(result))
+        }
+        // The default manager writes each function at its break and puts
+        // data at the other end of its buffer, so the code is everything
+        // between where the break was when the function started and now
+        let size = (default!(managed, get_break()) as usize).saturating_sub(managed.start);
+        if managed.manager.end_function(from_ptr(func), size) {
+            managed.sizes.insert(func as usize, size);
+            default!(managed, end_function(result))
+        } else {
+            default!(managed, end_function(JIT_MEMORY_TOO_BIG));
+            JIT_MEMORY_TOO_BIG
+        }
+    }
+}
+extern fn extend_limit(memctx: jit_memory_context_t, count: c_int) -> c_int {
+    unsafe {
+        let managed = managed(memctx);
+        if managed.manager.extend_limit(count as usize) {
+            default!(managed, extend_limit(count))
+        } else {
+            JIT_MEMORY_TOO_BIG
+        }
+    }
+}
+extern fn get_limit(memctx: jit_memory_context_t) -> *mut c_void {
+    unsafe { default!(managed(memctx), get_limit()) }
+}
+extern fn get_break(memctx: jit_memory_context_t) -> *mut c_void {
+    unsafe { default!(managed(memctx), get_break()) }
+}
+extern fn set_break(memctx: jit_memory_context_t, brk: *mut c_void) {
+    unsafe { default!(managed(memctx), set_break(brk)) }
+}
+extern fn alloc_trampoline(memctx: jit_memory_context_t) -> *mut c_void {
+    unsafe { default!(managed(memctx), alloc_trampoline()) }
+}
+extern fn free_trampoline(memctx: jit_memory_context_t, ptr: *mut c_void) {
+    unsafe { default!(managed(memctx), free_trampoline(ptr)) }
+}
+extern fn alloc_closure(memctx: jit_memory_context_t) -> *mut c_void {
+    unsafe { default!(managed(memctx), alloc_closure()) }
+}
+extern fn free_closure(memctx: jit_memory_context_t, ptr: *mut c_void) {
+    unsafe { default!(managed(memctx), free_closure(ptr)) }
+}
+extern fn alloc_data(memctx: jit_memory_context_t, size: jit_size_t, align: jit_size_t) -> *mut c_void {
+    unsafe {
+        let managed = managed(memctx);
+        match managed.manager.alloc_data(size as usize, align as usize) {
+            Some(data) => data,
+            None => default!(managed, alloc_data(size, align))
+        }
+    }
+}
+
+static MANAGER: Struct_jit_memory_manager = Struct_jit_memory_manager {
+    create: Some(create),
+    destroy: Some(destroy),
+    find_function_info: Some(find_function_info),
+    get_function: Some(get_function),
+    get_function_start: Some(get_function_start),
+    get_function_end: Some(get_function_end),
+    alloc_function: Some(alloc_function),
+    free_function: Some(free_function),
+    start_function: Some(start_function),
+    end_function: Some(end_function),
+    extend_limit: Some(extend_limit),
+    get_limit: Some(get_limit),
+    get_break: Some(get_break),
+    set_break: Some(set_break),
+    alloc_trampoline: Some(alloc_trampoline),
+    free_trampoline: Some(free_trampoline),
+    alloc_closure: Some(alloc_closure),
+    free_closure: Some(free_closure),
+    alloc_data: Some(alloc_data)
+};
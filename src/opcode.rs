@@ -0,0 +1,633 @@
+//! Typed instruction opcodes and the kinds of their operands
+use raw::*;
+use std::ffi::CStr;
+use std::os::raw::c_int;
+use std::ptr;
+use std::sync::Once;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+const JIT_OPCODE_DEST_MASK: c_int = 0x0000000F;
+const JIT_OPCODE_SRC1_SHIFT: c_int = 4;
+const JIT_OPCODE_SRC2_SHIFT: c_int = 8;
+pub const JIT_OPCODE_IS_BRANCH: c_int = 0x00001000;
+const JIT_OPCODE_IS_CALL: c_int = 0x00002000;
+const JIT_OPCODE_IS_CALL_EXTERNAL: c_int = 0x00004000;
+const JIT_OPCODE_IS_ADDROF_LABEL: c_int = 0x00010000;
+const JIT_OPCODE_IS_JUMP_TABLE: c_int = 0x00020000;
+
+opcodes!{
+    // Conversions
+    Nop => "nop",
+    TruncSbyte => "trunc_sbyte",
+    TruncUbyte => "trunc_ubyte",
+    TruncShort => "trunc_short",
+    TruncUshort => "trunc_ushort",
+    TruncInt => "trunc_int",
+    TruncUint => "trunc_uint",
+    CheckSbyte => "check_sbyte",
+    CheckUbyte => "check_ubyte",
+    CheckShort => "check_short",
+    CheckUshort => "check_ushort",
+    CheckInt => "check_int",
+    CheckUint => "check_uint",
+    LowWord => "low_word",
+    ExpandInt => "expand_int",
+    ExpandUint => "expand_uint",
+    CheckLowWord => "check_low_word",
+    CheckSignedLowWord => "check_signed_low_word",
+    CheckLong => "check_long",
+    CheckUlong => "check_ulong",
+    Float32ToInt => "float32_to_int",
+    Float32ToUint => "float32_to_uint",
+    Float32ToLong => "float32_to_long",
+    Float32ToUlong => "float32_to_ulong",
+    CheckFloat32ToInt => "check_float32_to_int",
+    CheckFloat32ToUint => "check_float32_to_uint",
+    CheckFloat32ToLong => "check_float32_to_long",
+    CheckFloat32ToUlong => "check_float32_to_ulong",
+    IntToFloat32 => "int_to_float32",
+    UintToFloat32 => "uint_to_float32",
+    LongToFloat32 => "long_to_float32",
+    UlongToFloat32 => "ulong_to_float32",
+    Float32ToFloat64 => "float32_to_float64",
+    Float32ToNFloat => "float32_to_nfloat",
+    Float64ToInt => "float64_to_int",
+    Float64ToUint => "float64_to_uint",
+    Float64ToLong => "float64_to_long",
+    Float64ToUlong => "float64_to_ulong",
+    CheckFloat64ToInt => "check_float64_to_int",
+    CheckFloat64ToUint => "check_float64_to_uint",
+    CheckFloat64ToLong => "check_float64_to_long",
+    CheckFloat64ToUlong => "check_float64_to_ulong",
+    IntToFloat64 => "int_to_float64",
+    UintToFloat64 => "uint_to_float64",
+    LongToFloat64 => "long_to_float64",
+    UlongToFloat64 => "ulong_to_float64",
+    Float64ToFloat32 => "float64_to_float32",
+    Float64ToNFloat => "float64_to_nfloat",
+    NFloatToInt => "nfloat_to_int",
+    NFloatToUint => "nfloat_to_uint",
+    NFloatToLong => "nfloat_to_long",
+    NFloatToUlong => "nfloat_to_ulong",
+    CheckNFloatToInt => "check_nfloat_to_int",
+    CheckNFloatToUint => "check_nfloat_to_uint",
+    CheckNFloatToLong => "check_nfloat_to_long",
+    CheckNFloatToUlong => "check_nfloat_to_ulong",
+    IntToNFloat => "int_to_nfloat",
+    UintToNFloat => "uint_to_nfloat",
+    LongToNFloat => "long_to_nfloat",
+    UlongToNFloat => "ulong_to_nfloat",
+    NFloatToFloat32 => "nfloat_to_float32",
+    NFloatToFloat64 => "nfloat_to_float64",
+    // Arithmetic
+    Iadd => "iadd",
+    IaddOvf => "iadd_ovf",
+    IaddOvfUn => "iadd_ovf_un",
+    Isub => "isub",
+    IsubOvf => "isub_ovf",
+    IsubOvfUn => "isub_ovf_un",
+    Imul => "imul",
+    ImulOvf => "imul_ovf",
+    ImulOvfUn => "imul_ovf_un",
+    Idiv => "idiv",
+    IdivUn => "idiv_un",
+    Irem => "irem",
+    IremUn => "irem_un",
+    Ineg => "ineg",
+    Ladd => "ladd",
+    LaddOvf => "ladd_ovf",
+    LaddOvfUn => "ladd_ovf_un",
+    Lsub => "lsub",
+    LsubOvf => "lsub_ovf",
+    LsubOvfUn => "lsub_ovf_un",
+    Lmul => "lmul",
+    LmulOvf => "lmul_ovf",
+    LmulOvfUn => "lmul_ovf_un",
+    Ldiv => "ldiv",
+    LdivUn => "ldiv_un",
+    Lrem => "lrem",
+    LremUn => "lrem_un",
+    Lneg => "lneg",
+    Fadd => "fadd",
+    Fsub => "fsub",
+    Fmul => "fmul",
+    Fdiv => "fdiv",
+    Frem => "frem",
+    FremIeee => "frem_ieee",
+    Fneg => "fneg",
+    Dadd => "dadd",
+    Dsub => "dsub",
+    Dmul => "dmul",
+    Ddiv => "ddiv",
+    Drem => "drem",
+    DremIeee => "drem_ieee",
+    Dneg => "dneg",
+    Nfadd => "nfadd",
+    Nfsub => "nfsub",
+    Nfmul => "nfmul",
+    Nfdiv => "nfdiv",
+    Nfrem => "nfrem",
+    NfremIeee => "nfrem_ieee",
+    Nfneg => "nfneg",
+    // Bitwise
+    Iand => "iand",
+    Ior => "ior",
+    Ixor => "ixor",
+    Inot => "inot",
+    Ishl => "ishl",
+    Ishr => "ishr",
+    IshrUn => "ishr_un",
+    Land => "land",
+    Lor => "lor",
+    Lxor => "lxor",
+    Lnot => "lnot",
+    Lshl => "lshl",
+    Lshr => "lshr",
+    LshrUn => "lshr_un",
+    // Branches
+    Br => "br",
+    BrIfalse => "br_ifalse",
+    BrItrue => "br_itrue",
+    BrIeq => "br_ieq",
+    BrIne => "br_ine",
+    BrIlt => "br_ilt",
+    BrIltUn => "br_ilt_un",
+    BrIle => "br_ile",
+    BrIleUn => "br_ile_un",
+    BrIgt => "br_igt",
+    BrIgtUn => "br_igt_un",
+    BrIge => "br_ige",
+    BrIgeUn => "br_ige_un",
+    BrLfalse => "br_lfalse",
+    BrLtrue => "br_ltrue",
+    BrLeq => "br_leq",
+    BrLne => "br_lne",
+    BrLlt => "br_llt",
+    BrLltUn => "br_llt_un",
+    BrLle => "br_lle",
+    BrLleUn => "br_lle_un",
+    BrLgt => "br_lgt",
+    BrLgtUn => "br_lgt_un",
+    BrLge => "br_lge",
+    BrLgeUn => "br_lge_un",
+    BrFeq => "br_feq",
+    BrFne => "br_fne",
+    BrFlt => "br_flt",
+    BrFle => "br_fle",
+    BrFgt => "br_fgt",
+    BrFge => "br_fge",
+    BrFltInv => "br_flt_inv",
+    BrFleInv => "br_fle_inv",
+    BrFgtInv => "br_fgt_inv",
+    BrFgeInv => "br_fge_inv",
+    BrDeq => "br_deq",
+    BrDne => "br_dne",
+    BrDlt => "br_dlt",
+    BrDle => "br_dle",
+    BrDgt => "br_dgt",
+    BrDge => "br_dge",
+    BrDltInv => "br_dlt_inv",
+    BrDleInv => "br_dle_inv",
+    BrDgtInv => "br_dgt_inv",
+    BrDgeInv => "br_dge_inv",
+    BrNfeq => "br_nfeq",
+    BrNfne => "br_nfne",
+    BrNflt => "br_nflt",
+    BrNfle => "br_nfle",
+    BrNfgt => "br_nfgt",
+    BrNfge => "br_nfge",
+    BrNfltInv => "br_nflt_inv",
+    BrNfleInv => "br_nfle_inv",
+    BrNfgtInv => "br_nfgt_inv",
+    BrNfgeInv => "br_nfge_inv",
+    // Comparisons
+    Icmp => "icmp",
+    IcmpUn => "icmp_un",
+    Lcmp => "lcmp",
+    LcmpUn => "lcmp_un",
+    Fcmpl => "fcmpl",
+    Fcmpg => "fcmpg",
+    Dcmpl => "dcmpl",
+    Dcmpg => "dcmpg",
+    Nfcmpl => "nfcmpl",
+    Nfcmpg => "nfcmpg",
+    Ieq => "ieq",
+    Ine => "ine",
+    Ilt => "ilt",
+    IltUn => "ilt_un",
+    Ile => "ile",
+    IleUn => "ile_un",
+    Igt => "igt",
+    IgtUn => "igt_un",
+    Ige => "ige",
+    IgeUn => "ige_un",
+    Leq => "leq",
+    Lne => "lne",
+    Llt => "llt",
+    LltUn => "llt_un",
+    Lle => "lle",
+    LleUn => "lle_un",
+    Lgt => "lgt",
+    LgtUn => "lgt_un",
+    Lge => "lge",
+    LgeUn => "lge_un",
+    Feq => "feq",
+    Fne => "fne",
+    Flt => "flt",
+    Fle => "fle",
+    Fgt => "fgt",
+    Fge => "fge",
+    FltInv => "flt_inv",
+    FleInv => "fle_inv",
+    FgtInv => "fgt_inv",
+    FgeInv => "fge_inv",
+    Deq => "deq",
+    Dne => "dne",
+    Dlt => "dlt",
+    Dle => "dle",
+    Dgt => "dgt",
+    Dge => "dge",
+    DltInv => "dlt_inv",
+    DleInv => "dle_inv",
+    DgtInv => "dgt_inv",
+    DgeInv => "dge_inv",
+    Nfeq => "nfeq",
+    Nfne => "nfne",
+    Nflt => "nflt",
+    Nfle => "nfle",
+    Nfgt => "nfgt",
+    Nfge => "nfge",
+    NfltInv => "nflt_inv",
+    NfleInv => "nfle_inv",
+    NfgtInv => "nfgt_inv",
+    NfgeInv => "nfge_inv",
+    IsFnan => "is_fnan",
+    IsFinf => "is_finf",
+    IsFfinite => "is_ffinite",
+    IsDnan => "is_dnan",
+    IsDinf => "is_dinf",
+    IsDfinite => "is_dfinite",
+    IsNfnan => "is_nfnan",
+    IsNfinf => "is_nfinf",
+    IsNffinite => "is_nffinite",
+    // Mathematical functions
+    Facos => "facos",
+    Fasin => "fasin",
+    Fatan => "fatan",
+    Fatan2 => "fatan2",
+    Fceil => "fceil",
+    Fcos => "fcos",
+    Fcosh => "fcosh",
+    Fexp => "fexp",
+    Ffloor => "ffloor",
+    Flog => "flog",
+    Flog10 => "flog10",
+    Fpow => "fpow",
+    Frint => "frint",
+    Fround => "fround",
+    Fsin => "fsin",
+    Fsinh => "fsinh",
+    Fsqrt => "fsqrt",
+    Ftan => "ftan",
+    Ftanh => "ftanh",
+    Ftrunc => "ftrunc",
+    Dacos => "dacos",
+    Dasin => "dasin",
+    Datan => "datan",
+    Datan2 => "datan2",
+    Dceil => "dceil",
+    Dcos => "dcos",
+    Dcosh => "dcosh",
+    Dexp => "dexp",
+    Dfloor => "dfloor",
+    Dlog => "dlog",
+    Dlog10 => "dlog10",
+    Dpow => "dpow",
+    Drint => "drint",
+    Dround => "dround",
+    Dsin => "dsin",
+    Dsinh => "dsinh",
+    Dsqrt => "dsqrt",
+    Dtan => "dtan",
+    Dtanh => "dtanh",
+    Dtrunc => "dtrunc",
+    Nfacos => "nfacos",
+    Nfasin => "nfasin",
+    Nfatan => "nfatan",
+    Nfatan2 => "nfatan2",
+    Nfceil => "nfceil",
+    Nfcos => "nfcos",
+    Nfcosh => "nfcosh",
+    Nfexp => "nfexp",
+    Nffloor => "nffloor",
+    Nflog => "nflog",
+    Nflog10 => "nflog10",
+    Nfpow => "nfpow",
+    Nfrint => "nfrint",
+    Nfround => "nfround",
+    Nfsin => "nfsin",
+    Nfsinh => "nfsinh",
+    Nfsqrt => "nfsqrt",
+    Nftan => "nftan",
+    Nftanh => "nftanh",
+    Nftrunc => "nftrunc",
+    Iabs => "iabs",
+    Labs => "labs",
+    Fabs => "fabs",
+    Dabs => "dabs",
+    Nfabs => "nfabs",
+    Imin => "imin",
+    IminUn => "imin_un",
+    Lmin => "lmin",
+    LminUn => "lmin_un",
+    Fmin => "fmin",
+    Dmin => "dmin",
+    Nfmin => "nfmin",
+    Imax => "imax",
+    ImaxUn => "imax_un",
+    Lmax => "lmax",
+    LmaxUn => "lmax_un",
+    Fmax => "fmax",
+    Dmax => "dmax",
+    Nfmax => "nfmax",
+    Isign => "isign",
+    Lsign => "lsign",
+    Fsign => "fsign",
+    Dsign => "dsign",
+    Nfsign => "nfsign",
+    // Calls and returns
+    CheckNull => "check_null",
+    Call => "call",
+    CallTail => "call_tail",
+    CallIndirect => "call_indirect",
+    CallIndirectTail => "call_indirect_tail",
+    CallVtablePtr => "call_vtable_ptr",
+    CallVtablePtrTail => "call_vtable_ptr_tail",
+    CallExternal => "call_external",
+    CallExternalTail => "call_external_tail",
+    Return => "return",
+    ReturnInt => "return_int",
+    ReturnLong => "return_long",
+    ReturnFloat32 => "return_float32",
+    ReturnFloat64 => "return_float64",
+    ReturnNFloat => "return_nfloat",
+    ReturnSmallStruct => "return_small_struct",
+    SetupForNested => "setup_for_nested",
+    SetupForSibling => "setup_for_sibling",
+    Import => "import",
+    // Exceptions
+    Throw => "throw",
+    Rethrow => "rethrow",
+    LoadPc => "load_pc",
+    LoadExceptionPc => "load_exception_pc",
+    EnterFinally => "enter_finally",
+    LeaveFinally => "leave_finally",
+    CallFinally => "call_finally",
+    EnterFilter => "enter_filter",
+    LeaveFilter => "leave_filter",
+    CallFilter => "call_filter",
+    CallFilterReturn => "call_filter_return",
+    AddressOfLabel => "address_of_label",
+    // Copies
+    CopyLoadSbyte => "copy_load_sbyte",
+    CopyLoadUbyte => "copy_load_ubyte",
+    CopyLoadShort => "copy_load_short",
+    CopyLoadUshort => "copy_load_ushort",
+    CopyInt => "copy_int",
+    CopyLong => "copy_long",
+    CopyFloat32 => "copy_float32",
+    CopyFloat64 => "copy_float64",
+    CopyNFloat => "copy_nfloat",
+    CopyStruct => "copy_struct",
+    CopyStoreByte => "copy_store_byte",
+    CopyStoreShort => "copy_store_short",
+    AddressOf => "address_of",
+    // Registers and parameters
+    IncomingReg => "incoming_reg",
+    IncomingFramePosn => "incoming_frame_posn",
+    OutgoingReg => "outgoing_reg",
+    OutgoingFramePosn => "outgoing_frame_posn",
+    ReturnReg => "return_reg",
+    PushInt => "push_int",
+    PushLong => "push_long",
+    PushFloat32 => "push_float32",
+    PushFloat64 => "push_float64",
+    PushNFloat => "push_nfloat",
+    PushStruct => "push_struct",
+    PopStack => "pop_stack",
+    FlushSmallStruct => "flush_small_struct",
+    SetParamInt => "set_param_int",
+    SetParamLong => "set_param_long",
+    SetParamFloat32 => "set_param_float32",
+    SetParamFloat64 => "set_param_float64",
+    SetParamNFloat => "set_param_nfloat",
+    SetParamStruct => "set_param_struct",
+    PushReturnAreaPtr => "push_return_area_ptr",
+    // Memory
+    LoadRelativeSbyte => "load_relative_sbyte",
+    LoadRelativeUbyte => "load_relative_ubyte",
+    LoadRelativeShort => "load_relative_short",
+    LoadRelativeUshort => "load_relative_ushort",
+    LoadRelativeInt => "load_relative_int",
+    LoadRelativeLong => "load_relative_long",
+    LoadRelativeFloat32 => "load_relative_float32",
+    LoadRelativeFloat64 => "load_relative_float64",
+    LoadRelativeNFloat => "load_relative_nfloat",
+    LoadRelativeStruct => "load_relative_struct",
+    StoreRelativeByte => "store_relative_byte",
+    StoreRelativeShort => "store_relative_short",
+    StoreRelativeInt => "store_relative_int",
+    StoreRelativeLong => "store_relative_long",
+    StoreRelativeFloat32 => "store_relative_float32",
+    StoreRelativeFloat64 => "store_relative_float64",
+    StoreRelativeNFloat => "store_relative_nfloat",
+    StoreRelativeStruct => "store_relative_struct",
+    AddRelative => "add_relative",
+    LoadElementSbyte => "load_element_sbyte",
+    LoadElementUbyte => "load_element_ubyte",
+    LoadElementShort => "load_element_short",
+    LoadElementUshort => "load_element_ushort",
+    LoadElementInt => "load_element_int",
+    LoadElementLong => "load_element_long",
+    LoadElementFloat32 => "load_element_float32",
+    LoadElementFloat64 => "load_element_float64",
+    LoadElementNFloat => "load_element_nfloat",
+    StoreElementByte => "store_element_byte",
+    StoreElementShort => "store_element_short",
+    StoreElementInt => "store_element_int",
+    StoreElementLong => "store_element_long",
+    StoreElementFloat32 => "store_element_float32",
+    StoreElementFloat64 => "store_element_float64",
+    StoreElementNFloat => "store_element_nfloat",
+    Memcpy => "memcpy",
+    Memmove => "memmove",
+    Memset => "memset",
+    Alloca => "alloca",
+    // Debugging and switches
+    MarkOffset => "mark_offset",
+    MarkBreakpoint => "mark_breakpoint",
+    JumpTable => "jump_table"
+}
+
+/// The kind of value an instruction takes or produces in one of its operands
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum OperandKind {
+    /// The operand is not used
+    Empty,
+    /// The operand is a 32-bit integer
+    Int,
+    /// The operand is a 64-bit integer
+    Long,
+    /// The operand is a 32-bit float
+    Float32,
+    /// The operand is a 64-bit float
+    Float64,
+    /// The operand is a native float
+    NFloat,
+    /// The operand can be a value of any type
+    Any
+}
+impl OperandKind {
+    fn from_bits(bits: c_int) -> OperandKind {
+        match bits & JIT_OPCODE_DEST_MASK {
+            1 => OperandKind::Int,
+            2 => OperandKind::Long,
+            3 => OperandKind::Float32,
+            4 => OperandKind::Float64,
+            5 => OperandKind::NFloat,
+            6 => OperandKind::Any,
+            _ => OperandKind::Empty
+        }
+    }
+}
+
+/// What an opcode expects in each of the operands of its instructions
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Operands {
+    /// The kind of the destination value
+    pub dest: OperandKind,
+    /// The kind of the first source value
+    pub value1: OperandKind,
+    /// The kind of the second source value
+    pub value2: OperandKind,
+    /// If the instruction refers to a label, either as a branch target or
+    /// by taking its address
+    pub label: bool,
+    /// If the instruction carries the signature of the function it calls
+    pub signature: bool,
+    /// If the instruction calls a native function pointer
+    pub native: bool
+}
+
+/// The opcodes in LibJIT's opcode table, matched up both ways
+struct Table {
+    /// The opcode at each index of `jit_opcodes`
+    opcodes: Vec<Option<Opcode>>,
+    /// The index in `jit_opcodes` of each opcode, in the order of `ALL`
+    raw: Vec<Option<i32>>
+}
+fn table() -> &'static Table {
+    static INIT: Once = Once::new();
+    static TABLE: AtomicPtr<Table> = AtomicPtr::new(0 as *mut Table);
+    INIT.call_once(|| {
+        let infos = unsafe { &*ptr::addr_of!(jit_opcodes) };
+        let opcodes: Vec<Option<Opcode>> = infos.iter().map(|info| {
+            if info.name.is_null() {
+                return None
+            }
+            let name = unsafe { CStr::from_ptr(info.name) }.to_bytes();
+            ALL.iter().cloned().find(|op| op.name().as_bytes() == name)
+        }).collect();
+        let mut raw = vec![None; ALL.len()];
+        for (index, op) in opcodes.iter().enumerate() {
+            if let Some(op) = *op {
+                raw[op as usize] = Some(index as i32);
+            }
+        }
+        // The table is never freed, so it can be borrowed for 'static
+        TABLE.store(Box::into_raw(Box::new(Table {
+            opcodes: opcodes,
+            raw: raw
+        })), Ordering::Release);
+    });
+    unsafe { &*TABLE.load(Ordering::Acquire) }
+}
+
+/// Opcodes are matched up with LibJIT's opcode table by name, so they stay
+/// correct even if LibJIT renumbers its opcodes between versions.
+impl Opcode {
+    /// Get the opcode with the number LibJIT gives it, or `None` if this
+    /// number isn't an opcode
+    pub fn from_raw(raw: i32) -> Option<Opcode> {
+        if raw < 0 {
+            None
+        } else {
+            table().opcodes.get(raw as usize).and_then(|op| *op)
+        }
+    }
+    /// Get the number LibJIT gives this opcode, or `None` if the version of
+    /// LibJIT this is linked with doesn't have it
+    pub fn to_raw(self) -> Option<i32> {
+        table().raw[self as usize]
+    }
+    /// Iterate through every opcode
+    pub fn all() -> ::std::iter::Cloned<::std::slice::Iter<'static, Opcode>> {
+        ALL.iter().cloned()
+    }
+    /// Get the raw flags LibJIT describes this opcode with
+    fn flags(self) -> c_int {
+        match self.to_raw() {
+            Some(raw) => unsafe { jit_opcodes[raw as usize].flags },
+            None => 0
+        }
+    }
+    /// Check if instructions with this opcode branch to a label
+    pub fn is_branch(self) -> bool {
+        self.flags() & JIT_OPCODE_IS_BRANCH != 0
+    }
+    /// Check if instructions with this opcode call a function
+    pub fn is_call(self) -> bool {
+        self.flags() & (JIT_OPCODE_IS_CALL | JIT_OPCODE_IS_CALL_EXTERNAL) != 0
+    }
+    /// Check if instructions with this opcode return from the function
+    ///
+    /// LibJIT's flags have no bit for returns, so they are the opcodes its
+    /// table names `return_*`, apart from `return_reg`, which only marks the
+    /// register a call's result comes back in.
+    pub fn is_return(self) -> bool {
+        let name = self.name();
+        self.to_raw().is_some() && name.starts_with("return") && !name.ends_with("_reg")
+    }
+    /// Check if instructions with this opcode never let control fall through
+    /// to the next instruction
+    ///
+    /// These are returns, throws, tail calls and branches without a
+    /// condition, other than `call_finally`, which comes back once the
+    /// `finally` block is done.
+    pub fn is_terminator(self) -> bool {
+        let name = self.name();
+        let operands = self.operands();
+        let unconditional = operands.value1 == OperandKind::Empty && operands.value2 == OperandKind::Empty;
+        self.is_return()
+            || self.to_raw().is_some() && name.ends_with("throw")
+            || self.is_call() && name.ends_with("_tail")
+            || self.is_branch() && unconditional && !name.starts_with("call_")
+    }
+    /// Get what this opcode expects in each of the operands of its instructions
+    pub fn operands(self) -> Operands {
+        let flags = self.flags();
+        Operands {
+            dest: OperandKind::from_bits(flags),
+            value1: OperandKind::from_bits(flags >> JIT_OPCODE_SRC1_SHIFT),
+            value2: OperandKind::from_bits(flags >> JIT_OPCODE_SRC2_SHIFT),
+            label: flags & (JIT_OPCODE_IS_BRANCH | JIT_OPCODE_IS_ADDROF_LABEL | JIT_OPCODE_IS_JUMP_TABLE) != 0,
+            signature: match self {
+                Opcode::CallIndirect | Opcode::CallIndirectTail
+                | Opcode::CallVtablePtr | Opcode::CallVtablePtrTail
+                | Opcode::CallExternal | Opcode::CallExternalTail => true,
+                _ => false
+            },
+            native: flags & JIT_OPCODE_IS_CALL_EXTERNAL != 0
+        }
+    }
+}
@@ -0,0 +1,268 @@
+//! Compiling functions in the background on a pool of threads
+use raw::*;
+use closure::Signature;
+use context::{Context, SyncContext};
+use function::UncompiledFunction;
+use types::get;
+use std::error::Error;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{self, Poll, Waker};
+use std::thread::{self, JoinHandle};
+use std::{fmt, mem};
+
+type Job = Box<FnOnce(&Arc<SyncContext>) + Send>;
+
+/// A function compiled by a `CompileQueue`, which can be called from any
+/// thread for as long as this handle is alive
+pub struct Compiled<S> where S: Signature {
+    _context: Arc<SyncContext>,
+    ptr: *mut c_void,
+    marker: PhantomData<S>
+}
+// `ptr` is only a code address, which is never written through, and the
+// code behind it stays valid while `_context` is alive. The context is a
+// `SyncContext`, so it is safe to share and to drop on any thread.
+unsafe impl<S> Send for Compiled<S> where S: Signature {}
+unsafe impl<S> Sync for Compiled<S> where S: Signature {}
+impl<S> Compiled<S> where S: Signature {
+    /// Get the native function pointer that calls the compiled code
+    pub fn as_fn(&self) -> S::Extern {
+        unsafe {
+            mem::transmute_copy(&self.ptr)
+        }
+    }
+    /// Get the address of the compiled code's entry point
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.ptr
+    }
+}
+
+/// The reason a `CompileQueue` couldn't compile a function
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum QueueError {
+    /// LibJIT failed to compile the function
+    Failed,
+    /// The builder panicked
+    Panicked
+}
+impl fmt::Display for QueueError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.description())
+    }
+}
+impl fmt::Debug for QueueError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.description())
+    }
+}
+impl Error for QueueError {
+    fn description(&self) -> &'static str {
+        match *self {
+            QueueError::Failed => "Failed to compile the function",
+            QueueError::Panicked => "The function's builder panicked"
+        }
+    }
+}
+
+/// The result of a compile that a worker hands back to its future
+struct Slot<S> where S: Signature {
+    result: Option<Result<Compiled<S>, QueueError>>,
+    waker: Option<Waker>
+}
+type Shared<S> = Mutex<Slot<S>>;
+
+/// Resolves a future when it is dropped, with a failure if nothing else
+/// resolved it first, so futures still resolve if a builder panics
+struct Completer<S> where S: Signature {
+    shared: Option<Arc<Shared<S>>>
+}
+impl<S> Completer<S> where S: Signature {
+    fn complete(&mut self, result: Result<Compiled<S>, QueueError>) {
+        if let Some(shared) = self.shared.take() {
+            let mut slot = shared.lock().unwrap();
+            slot.result = Some(result);
+            if let Some(waker) = slot.waker.take() {
+                waker.wake()
+            }
+        }
+    }
+}
+impl<S> Drop for Completer<S> where S: Signature {
+    fn drop(&mut self) {
+        self.complete(Err(QueueError::Panicked))
+    }
+}
+
+/// A function being compiled by a `CompileQueue`, which resolves once it
+/// has been compiled
+pub struct CompileFuture<S> where S: Signature {
+    shared: Arc<Shared<S>>
+}
+impl<S> Future for CompileFuture<S> where S: Signature {
+    type Output = Result<Compiled<S>, QueueError>;
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context) -> Poll<Self::Output> {
+        let mut slot = self.shared.lock().unwrap();
+        match slot.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// The jobs waiting for a worker, and whether the queue has been dropped
+struct Jobs {
+    queue: Mutex<(VecDeque<Job>, bool)>,
+    ready: Condvar
+}
+
+/// A pool of threads that build and compile functions without blocking the
+/// thread that asks for them
+///
+/// Each thread has a context of its own, since LibJIT only lets one thread
+/// build in a context at a time. A function stays valid for as long as its
+/// `Compiled` handle is alive, even after the queue is dropped.
+///
+/// ```rust
+/// extern crate jit;
+/// use jit::*;
+/// use std::future::Future;
+/// use std::pin::Pin;
+/// use std::sync::Arc;
+/// use std::task::{Context, Poll, Wake};
+/// use std::thread::{self, Thread};
+///
+/// struct Unpark(Thread);
+/// impl Wake for Unpark {
+///     fn wake(self: Arc<Self>) {
+///         self.0.unpark()
+///     }
+/// }
+/// fn block_on<F: Future>(mut future: F) -> F::Output {
+///     let waker = Arc::new(Unpark(thread::current())).into();
+///     let mut cx = Context::from_waker(&waker);
+///     let mut future = unsafe { Pin::new_unchecked(&mut future) };
+///     loop {
+///         match future.as_mut().poll(&mut cx) {
+///             Poll::Ready(output) => return output,
+///             Poll::Pending => thread::park()
+///         }
+///     }
+/// }
+///
+/// fn main() {
+///     if let Some(queue) = CompileQueue::new(2) {
+///         let square = queue.compile::<fn(i32) -> i32, _>(|func| {
+///             func.insn_return(func.insn_mul(&func[0], &func[0]));
+///         });
+///         let square = block_on(square).unwrap();
+///         assert_eq!(square.as_fn()(7), 49);
+///     }
+/// }
+/// ```
+pub struct CompileQueue {
+    jobs: Arc<Jobs>,
+    workers: Vec<JoinHandle<()>>
+}
+impl CompileQueue {
+    /// Make a queue that compiles functions on `threads` threads, each with a
+    /// new context, or return `None` if LibJIT was built without thread support
+    pub fn new(threads: usize) -> Option<CompileQueue> {
+        let contexts: Option<Vec<_>> = (0..threads.max(1)).map(|_| Context::new_sync()).collect();
+        contexts.map(CompileQueue::with_contexts)
+    }
+    /// Make a queue that compiles functions on one thread for each of `contexts`
+    ///
+    /// This panics if `contexts` is empty.
+    pub fn with_contexts(contexts: Vec<SyncContext>) -> CompileQueue {
+        assert!(!contexts.is_empty(), "A compile queue needs at least one context");
+        let jobs = Arc::new(Jobs {
+            queue: Mutex::new((VecDeque::new(), false)),
+            ready: Condvar::new()
+        });
+        let workers = contexts.into_iter().enumerate().map(|(index, context)| {
+            let jobs = jobs.clone();
+            let context = Arc::new(context);
+            thread::Builder::new()
+                .name(format!("jit-compile-{}", index))
+                .spawn(move || work(&jobs, &context))
+                .unwrap()
+        }).collect();
+        CompileQueue {
+            jobs: jobs,
+            workers: workers
+        }
+    }
+    /// Build a function with the signature `S` using `builder` on one of the
+    /// queue's threads, then compile it
+    ///
+    /// The future this returns resolves once the function has been compiled,
+    /// or with `QueueError::Failed` if it failed to compile, or
+    /// `QueueError::Panicked` if `builder` panicked.
+    pub fn compile<S, F>(&self, builder: F) -> CompileFuture<S>
+        where S: Signature + 'static, F: FnOnce(&UncompiledFunction) + Send + 'static {
+        let shared = Arc::new(Mutex::new(Slot {
+            result: None,
+            waker: None
+        }));
+        let mut completer = Completer {
+            shared: Some(shared.clone())
+        };
+        let job: Job = Box::new(move |context: &Arc<SyncContext>| {
+            let result = context.build(|ctx| {
+                let func = UncompiledFunction::new(ctx, &get::<S>());
+                builder(&func);
+                match UncompiledFunction::compile(func) {
+                    Ok(func) => Ok(unsafe { jit_function_to_closure((&*func).into()) }),
+                    Err(_) => Err(QueueError::Failed)
+                }
+            });
+            completer.complete(result.map(|ptr| Compiled {
+                _context: context.clone(),
+                ptr: ptr,
+                marker: PhantomData
+            }));
+        });
+        self.jobs.queue.lock().unwrap().0.push_back(job);
+        self.jobs.ready.notify_one();
+        CompileFuture {
+            shared: shared
+        }
+    }
+}
+impl Drop for CompileQueue {
+    /// Finish compiling the functions already queued, then stop the threads
+    fn drop(&mut self) {
+        self.jobs.queue.lock().unwrap().1 = true;
+        self.jobs.ready.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Run jobs in `context` until the queue is dropped and no jobs are left
+fn work(jobs: &Jobs, context: &Arc<SyncContext>) {
+    loop {
+        let job = {
+            let mut queue = jobs.queue.lock().unwrap();
+            loop {
+                match queue.0.pop_front() {
+                    Some(job) => break job,
+                    None if queue.1 => return,
+                    // Waiting releases the lock, so other workers can take jobs
+                    None => queue = jobs.ready.wait(queue).unwrap()
+                }
+            }
+        };
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| job(context)));
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Iter;
+use std::fmt;
+
+/// A location in the source code that some JIT code was generated from
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct SourceLocation {
+    /// The name of the file
+    pub file: String,
+    /// The line in the file, starting at 1
+    pub line: u32,
+    /// The column in the line, starting at 1
+    pub column: u32
+}
+impl SourceLocation {
+    /// Create a new source location
+    pub fn new(file: &str, line: u32, column: u32) -> SourceLocation {
+        SourceLocation {
+            file: file.to_owned(),
+            line: line,
+            column: column
+        }
+    }
+}
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+impl fmt::Debug for SourceLocation {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, fmt)
+    }
+}
+
+/// Maps the offsets marked in a function with `insn_mark_location` to the
+/// source locations they were generated from
+#[derive(Clone, Default)]
+pub struct SourceMap {
+    locations: HashMap<u32, SourceLocation>
+}
+impl SourceMap {
+    /// Create an empty source map
+    pub fn new() -> SourceMap {
+        SourceMap {
+            locations: HashMap::new()
+        }
+    }
+    /// Map the offset given to a source location
+    pub fn insert(&mut self, offset: u32, location: SourceLocation) {
+        self.locations.insert(offset, location);
+    }
+    /// Get the source location of the offset given
+    pub fn get(&self, offset: u32) -> Option<&SourceLocation> {
+        self.locations.get(&offset)
+    }
+    /// Get the number of offsets in this map
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+    /// Check if this map has no offsets
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+    /// Iterate through the offsets and their source locations
+    pub fn iter(&self) -> Iter<u32, SourceLocation> {
+        self.locations.iter()
+    }
+}
@@ -0,0 +1,143 @@
+use context::{Context, ContextMember};
+use function::{CallFlags, CompileError, CompiledFunction, UncompiledFunction};
+use types::Ty;
+use cbox::CSemiBox;
+use std::cell::Cell;
+use std::cmp;
+use std::ops::Deref;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// The state shared between a `TieredFunction` and the prologue of its first tier
+struct Tiers<'a> {
+    func: Cell<*const CompiledFunction>,
+    calls: AtomicUsize,
+    threshold: usize,
+    attempted: AtomicBool,
+    promoted: AtomicBool,
+    builder: Box<Fn(&UncompiledFunction) + 'a>
+}
+impl<'a> Tiers<'a> {
+    fn promote(&self) -> bool {
+        let func = unsafe { &*self.func.get() };
+        let builder = &self.builder;
+        let promoted = func.recompile(UncompiledFunction::get_max_optimization_level(), |func| builder(func));
+        if promoted {
+            self.promoted.store(true, Ordering::SeqCst);
+        }
+        promoted
+    }
+}
+/// Called by the prologue of the first tier, which promotes the function
+/// on the first call after it becomes hot that isn't made while the current
+/// thread holds the build lock
+extern fn count_call(tiers: usize) {
+    let tiers = unsafe { &*(tiers as *const Tiers) };
+    if tiers.calls.fetch_add(1, Ordering::SeqCst) + 1 < tiers.threshold {
+        return
+    }
+    // Promoting takes the build lock, which would deadlock here, so leave
+    // it to a later call
+    let func = unsafe { &*tiers.func.get() };
+    if func.get_context().is_building() {
+        return
+    }
+    if !tiers.attempted.swap(true, Ordering::SeqCst) {
+        // Unwinding through JIT frames isn't possible, so a panicking builder
+        // just leaves the first tier in place
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| tiers.promote()));
+    }
+}
+
+/// A function which is compiled quickly at first, then recompiled at the
+/// highest optimization level once it has been called enough times
+///
+/// The first version of the function counts how many times it is called
+/// in its prologue. The call that makes the count reach the threshold
+/// rebuilds the function without the prologue and swaps the new code in
+/// before it returns. Closures made with `as_func` stay valid throughout.
+///
+/// Promotion takes the build lock of the context, so calls made while the
+/// current thread holds it, such as from inside `Context::build`, don't
+/// promote the function. The first call after that which does promotes it.
+///
+/// ```rust
+/// use jit::*;
+/// let mut ctx = Context::<()>::new();
+/// let func = TieredFunction::new(&mut ctx, &get::<fn(i32) -> i32>(), 2, |func| {
+///     let x = &func[0];
+///     func.insn_return(func.insn_mul(x, x));
+/// }).unwrap();
+/// let square: extern fn(i32) -> i32 = func.as_func();
+/// assert_eq!(square(3), 9);
+/// assert!(!func.is_promoted());
+/// assert_eq!(square(4), 16);
+/// assert!(func.is_promoted());
+/// assert_eq!(square(5), 25);
+/// ```
+pub struct TieredFunction<'a> {
+    func: CSemiBox<'a, CompiledFunction>,
+    tiers: Box<Tiers<'a>>
+}
+impl<'a> TieredFunction<'a> {
+    /// Create a function with the signature given whose body is built by
+    /// `builder`, and which is promoted after `threshold` calls, or return
+    /// the error if the first version of it fails to compile
+    pub fn new<T, F>(context: &'a Context<T>, signature: &Ty, threshold: usize, builder: F) -> Result<TieredFunction<'a>, CompileError>
+        where F: Fn(&UncompiledFunction) + 'a {
+        let tiers = Box::new(Tiers {
+            func: Cell::new(ptr::null()),
+            calls: AtomicUsize::new(0),
+            threshold: cmp::max(threshold, 1),
+            attempted: AtomicBool::new(false),
+            promoted: AtomicBool::new(false),
+            builder: Box::new(builder)
+        });
+        let func = UncompiledFunction::new(context, signature);
+        func.set_recompilable();
+        func.set_optimization_level(0);
+        {
+            let state = func.insn_of(&*tiers as *const Tiers as usize);
+            func.insn_call_rust(Some("count_call"), count_call, &[state], CallFlags::NO_THROW);
+            (tiers.builder)(&func);
+        }
+        let func = try!(UncompiledFunction::compile(func));
+        tiers.func.set(&*func);
+        Ok(TieredFunction {
+            func: func,
+            tiers: tiers
+        })
+    }
+    /// Get the number of times the first version of this function has been called
+    pub fn get_calls(&self) -> usize {
+        self.tiers.calls.load(Ordering::SeqCst)
+    }
+    /// Check if this function has been called enough times to be promoted
+    pub fn is_hot(&self) -> bool {
+        self.get_calls() >= self.tiers.threshold
+    }
+    /// Check if this function has been promoted
+    pub fn is_promoted(&self) -> bool {
+        self.tiers.promoted.load(Ordering::SeqCst)
+    }
+    /// Recompile this function at the highest optimization level without
+    /// the call counter, whether or not it is hot
+    pub fn promote(&self) -> bool {
+        self.tiers.promote()
+    }
+    /// Promote this function if it is hot and hasn't been promoted already,
+    /// and return true if it was promoted
+    ///
+    /// This is only needed if promoting it when it became hot failed, or if
+    /// it hasn't been called since it became hot.
+    pub fn promote_if_hot(&self) -> bool {
+        self.is_hot() && !self.is_promoted() && self.promote()
+    }
+}
+impl<'a> Deref for TieredFunction<'a> {
+    type Target = CompiledFunction;
+    fn deref(&self) -> &CompiledFunction {
+        &self.func
+    }
+}
@@ -0,0 +1,187 @@
+use raw::*;
+use context::Context;
+use function::Func;
+use source::SourceLocation;
+use util::from_ptr_opt;
+use std::os::raw::{c_uint, c_void};
+use std::marker::PhantomData;
+use std::{fmt, mem};
+
+/// The offset LibJIT gives to frames which have no offset marked
+const JIT_NO_OFFSET: c_uint = !0;
+
+fn offset_opt(offset: c_uint) -> Option<u32> {
+    if offset == JIT_NO_OFFSET {
+        None
+    } else {
+        Some(offset as u32)
+    }
+}
+
+/// A single frame of a stack trace
+#[derive(Clone, Copy)]
+pub struct StackFrame<'a> {
+    /// The function this frame is in, if it was compiled by LibJIT
+    pub function: Option<&'a Func>,
+    /// The last offset marked with `insn_mark_offset` before this frame's
+    /// program counter, if there is one
+    pub offset: Option<u32>,
+    /// The native program counter of this frame
+    pub pc: *mut c_void
+}
+impl<'a> StackFrame<'a> {
+    /// Get the source location this frame's offset was marked with using
+    /// `insn_mark_location`
+    pub fn get_location(&self) -> Option<&'a SourceLocation> {
+        match (self.function, self.offset) {
+            (Some(func), Some(offset)) => func.get_location(offset),
+            _ => None
+        }
+    }
+}
+impl<'a> fmt::Debug for StackFrame<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match (self.function, self.offset) {
+            (Some(func), Some(offset)) => match self.get_location() {
+                Some(location) => write!(fmt, "{:?} in {:?} at {}", self.pc, func.get_signature(), location),
+                None => write!(fmt, "{:?} in {:?} at offset {}", self.pc, func.get_signature(), offset)
+            },
+            (Some(func), None) => write!(fmt, "{:?} in {:?}", self.pc, func.get_signature()),
+            (None, _) => write!(fmt, "{:?} in native code", self.pc)
+        }
+    }
+}
+
+/// A stack trace captured from JIT-compiled code
+///
+/// This can be captured inside an exception handler, or from Rust code that
+/// has been called by JIT code with `insn_call_rust`.
+pub struct StackTrace {
+    _trace: jit_stack_trace_t
+}
+native_ref!(StackTrace, _trace: jit_stack_trace_t);
+impl StackTrace {
+    /// Capture the stack trace of the current thread
+    pub fn capture() -> Option<StackTrace> {
+        unsafe {
+            from_ptr_opt(jit_exception_get_stack_trace())
+        }
+    }
+    /// Get the number of frames in this trace
+    pub fn len(&self) -> usize {
+        unsafe {
+            jit_stack_trace_get_size(self.into()) as usize
+        }
+    }
+    /// Check if this trace has no frames
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Get the frame at `index`, resolving its function in the context given
+    pub fn get<'a, T>(&'a self, ctx: &'a Context<T>, index: usize) -> Option<StackFrame<'a>> {
+        if index >= self.len() {
+            return None
+        }
+        unsafe {
+            let posn = index as c_uint;
+            Some(StackFrame {
+                function: from_ptr_opt(jit_stack_trace_get_function(ctx.into(), self.into(), posn)),
+                offset: offset_opt(jit_stack_trace_get_offset(ctx.into(), self.into(), posn)),
+                pc: jit_stack_trace_get_pc(self.into(), posn)
+            })
+        }
+    }
+    /// Iterate through the frames in this trace, resolving their functions in
+    /// the context given
+    pub fn frames<'a, T>(&'a self, ctx: &'a Context<T>) -> StackFrames<'a, T> {
+        StackFrames {
+            trace: self,
+            context: ctx,
+            index: 0
+        }
+    }
+}
+impl Drop for StackTrace {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe {
+            jit_stack_trace_free(self.into())
+        }
+    }
+}
+
+/// Iterates through the frames of a `StackTrace`
+pub struct StackFrames<'a, T: 'a> {
+    trace: &'a StackTrace,
+    context: &'a Context<T>,
+    index: usize
+}
+impl<'a, T> Iterator for StackFrames<'a, T> {
+    type Item = StackFrame<'a>;
+    fn next(&mut self) -> Option<StackFrame<'a>> {
+        let frame = self.trace.get(self.context, self.index);
+        if frame.is_some() {
+            self.index += 1;
+        }
+        frame
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.trace.len() - self.index;
+        (left, Some(left))
+    }
+}
+
+/// Walks the stack of the current thread one frame at a time, without
+/// capturing the whole trace first
+pub struct Unwinder<'a> {
+    _unwind: jit_unwind_context_t,
+    started: bool,
+    marker: PhantomData<&'a ()>
+}
+impl<'a> Unwinder<'a> {
+    /// Start unwinding from the current frame, resolving functions in the
+    /// context given
+    pub fn new<T>(ctx: &'a Context<T>) -> Option<Unwinder<'a>> {
+        unsafe {
+            let mut unwind: jit_unwind_context_t = mem::zeroed();
+            if jit_unwind_init(&mut unwind, ctx.into()) == 0 {
+                None
+            } else {
+                Some(Unwinder {
+                    _unwind: unwind,
+                    started: false,
+                    marker: PhantomData
+                })
+            }
+        }
+    }
+    /// Get the frame the unwinder is currently at
+    pub fn current(&mut self) -> StackFrame<'a> {
+        unsafe {
+            StackFrame {
+                function: from_ptr_opt(jit_unwind_get_function(&mut self._unwind)),
+                offset: offset_opt(jit_unwind_get_offset(&mut self._unwind)),
+                pc: jit_unwind_get_pc(&mut self._unwind)
+            }
+        }
+    }
+}
+impl<'a> Iterator for Unwinder<'a> {
+    type Item = StackFrame<'a>;
+    fn next(&mut self) -> Option<StackFrame<'a>> {
+        if self.started && unsafe { jit_unwind_next(&mut self._unwind) } == 0 {
+            return None
+        }
+        self.started = true;
+        Some(self.current())
+    }
+}
+impl<'a> Drop for Unwinder<'a> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe {
+            jit_unwind_free(&mut self._unwind)
+        }
+    }
+}
@@ -2,7 +2,7 @@ use raw::*;
 use compile::Compile;
 use function::Abi;
 use  std::os::raw::{c_char, c_uint, c_int, c_void};
-use util::{from_ptr, from_ptr_opt};
+use util::{self, from_ptr, from_ptr_opt};
 use std::borrow::*;
 use std::marker::PhantomData;
 use std::{fmt, mem, str};
@@ -116,11 +116,24 @@ impl fmt::Debug for Ty {
         }
     }
 }
+/// Writes the type the way LibJIT writes it in function dumps
+impl fmt::Display for Ty {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", try!(util::dump(|fd| unsafe {
+            jit_dump_type(fd as *mut c_void, self.into());
+        })))
+    }
+}
 impl fmt::Debug for Type {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         fmt::Debug::fmt(self.deref(), fmt)
     }
 }
+impl fmt::Display for Type {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.deref(), fmt)
+    }
+}
 /// Type constants
 pub mod consts {
     use util::from_ptr;
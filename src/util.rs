@@ -1,10 +1,32 @@
 use libc::*;
 use std::fmt::Error;
 use std::ffi::CStr;
-use std::{mem, ptr, str};
+use std::{mem, ptr};
 use std::ops::{Deref, Drop};
+use raw::{jit_function_get_meta, jit_function_set_meta};
 use compile::Compile;
+use function::UncompiledFunction;
 use types::Ty;
+use std::any::Any;
+/// The metadata key of a function's `SourceMap`
+///
+/// LibJIT reserves keys of 10000 and above, so the keys used by this crate
+/// count down from there.
+pub const SOURCE_MAP_META: c_int = 9999;
+/// The metadata key of a context's debugger hook
+pub const DEBUGGER_HOOK_META: c_int = 9998;
+/// The metadata key of the closure that builds a function on demand
+pub const ON_DEMAND_META: c_int = 9997;
+/// The metadata key of a context's on-demand driver
+pub const ON_DEMAND_DRIVER_META: c_int = 9996;
+/// The metadata key of the values a function keeps alive for its code
+pub const KEEP_ALIVE_META: c_int = 9995;
+/// The metadata key of a context's memory manager, until LibJIT takes it
+pub const MEMORY_MANAGER_META: c_int = 9994;
+/// The metadata key of the try regions a function's catcher dispatches between
+pub const CATCHER_META: c_int = 9993;
+/// The result LibJIT callbacks return when they succeed
+pub const JIT_RESULT_OK: c_int = 1;
 pub fn oom() -> ! {
     panic!("out of memory")
 }
@@ -30,35 +52,70 @@ pub fn assert_sig<'a, A, R>(sig: &Ty) where A: Compile<'a>, R: Compile<'a> {
 pub fn assert_sig<'a, A, R>(_: &Ty) where A: Compile<'a>, R: Compile<'a> {
 }
 
+/// Run `cb` with a stream that writes into memory, then get what it wrote
+#[cfg(unix)]
 pub fn dump<F>(cb: F) -> Result<String, Error> where F:FnOnce(*mut FILE) {
     unsafe {
-        let mut pair = [0, 0];
-        if pipe(pair.as_mut_ptr()) == -1 {
-            return Err(Error)
-        }
-        let file = fdopen(pair[1], b"w".as_ptr() as *const c_char);
+        let mut buf: *mut c_char = ptr::null_mut();
+        let mut len: size_t = 0;
+        let file = open_memstream(&mut buf, &mut len);
         if file.is_null() {
             return Err(Error)
         }
         cb(file);
         fclose(file);
-        let file = fdopen(pair[0], b"r".as_ptr() as *const c_char);
+        let text = String::from_utf8_lossy(::std::slice::from_raw_parts(buf as *const u8, len)).into_owned();
+        free(buf as *mut c_void);
+        Ok(text)
+    }
+}
+/// Run `cb` with a temporary file to write to, then get what it wrote
+///
+/// `open_memstream` is only available on POSIX systems, so this reads the
+/// text back from a file that is deleted when it is closed instead.
+#[cfg(not(unix))]
+pub fn dump<F>(cb: F) -> Result<String, Error> where F:FnOnce(*mut FILE) {
+    unsafe {
+        let file = tmpfile();
         if file.is_null() {
             return Err(Error)
         }
-        let mut chars:[c_char; 64] = mem::zeroed();
-        let mut text = String::new();
+        cb(file);
+        rewind(file);
+        let mut bytes = Vec::new();
+        let mut chunk = [0u8; 1024];
         loop {
-            let ptr = fgets(chars.as_mut_ptr(), chars.len() as c_int, file);
-            let bytes = chars.split(|&c| c == 0).next().unwrap();
-            let bytes = mem::transmute(bytes);
-            text.push_str(str::from_utf8_unchecked(bytes));
-            if ptr.is_null() {
+            let read = fread(chunk.as_mut_ptr() as *mut c_void, 1, chunk.len() as size_t, file);
+            bytes.extend_from_slice(&chunk[..read as usize]);
+            if read < chunk.len() as size_t {
                 break
             }
         }
+        let failed = ferror(file) != 0;
         fclose(file);
-        Ok(text)
+        if failed {
+            Err(Error)
+        } else {
+            Ok(String::from_utf8_lossy(&bytes).into_owned())
+        }
+    }
+}
+/// Move `data` into `func` so it lives as long as the function does, and
+/// get a reference to it that code built in the function can use
+pub fn keep_alive<T>(func: &UncompiledFunction, data: T) -> &T where T: Any {
+    unsafe {
+        let mut values = jit_function_get_meta(func.into(), KEEP_ALIVE_META) as *mut Vec<Box<Any>>;
+        if values.is_null() {
+            let new_values: Box<Vec<Box<Any>>> = Box::new(Vec::new());
+            values = mem::transmute(new_values);
+            if jit_function_set_meta(func.into(), KEEP_ALIVE_META, values as *mut _, Some(::free_data::<Vec<Box<Any>>>), 0) == 0 {
+                oom()
+            }
+        }
+        let data = Box::new(data);
+        let data_ptr: *const T = &*data;
+        (*values).push(data);
+        &*data_ptr
     }
 }
 pub fn from_ptr_opt<R, T>(ptr: *mut T) -> Option<R> where R:From<*mut T> {
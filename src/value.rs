@@ -124,6 +124,18 @@ impl<'a> Index<&'a str> for Val {
         }
     }
 }
+impl<'a> Index<&'a Val> for Val {
+    type Output = Val;
+    fn index(&self, index: &'a Val) -> &Val {
+        let func = self.get_function();
+        let ty = self.get_type();
+        if let Some(elem) = ty.get_ref() {
+            func.insn_load_elem(self, index, elem)
+        } else {
+            panic!("{:?} cannot be indexed by a value", ty)
+        }
+    }
+}
 macro_rules! bin_op {
     ($trait_ty:ident, $trait_func:ident, $assign_ty:ident, $assign_func:ident, $func:ident) => (
         impl<'a> $trait_ty<&'a Val> for &'a Val {
@@ -0,0 +1,226 @@
+//! Checking functions for mistakes LibJIT doesn't catch before compiling them
+use raw::*;
+use function::UncompiledFunction;
+use insn::{Block, Instruction};
+use opcode::{Opcode, OperandKind};
+use types::{Ty, Type, TypeKind};
+use std::error::Error;
+use std::{fmt, mem};
+
+/// A mistake found in a function by `UncompiledFunction::verify`
+#[derive(Clone, PartialEq)]
+pub enum VerifyError {
+    /// An operand of an instruction has a type its opcode can't take
+    OperandType {
+        /// The instruction, as it appears in the function's dump
+        insn: String,
+        /// The operand, which is one of `dest`, `value1` or `value2`
+        operand: &'static str,
+        /// The kind of value the opcode takes in this operand
+        expected: OperandKind,
+        /// The type of the value given
+        got: Type
+    },
+    /// A value is stored into memory that holds a different type
+    StoreType {
+        /// The instruction, as it appears in the function's dump
+        insn: String,
+        /// The type of the memory being stored into
+        expected: Type,
+        /// The type of the value being stored
+        got: Type
+    },
+    /// A branch targets a label that is never placed in the function
+    UndefinedLabel {
+        /// The instruction, as it appears in the function's dump
+        insn: String,
+        /// The label branched to
+        label: u64
+    },
+    /// Control can reach the end of a block at the end of the function
+    /// without returning a value
+    MissingReturn {
+        /// The index of the block in `UncompiledFunction::blocks`
+        block: usize
+    }
+}
+impl fmt::Display for VerifyError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VerifyError::OperandType { ref insn, operand, expected, ref got } =>
+                write!(fmt, "Operand {} of `{}` should be {:?}, but got {:?}", operand, insn.trim(), expected, got),
+            VerifyError::StoreType { ref insn, ref expected, ref got } =>
+                write!(fmt, "`{}` stores {:?} into memory that holds {:?}", insn.trim(), got, expected),
+            VerifyError::UndefinedLabel { ref insn, label } =>
+                write!(fmt, "`{}` branches to label {}, which is never placed", insn.trim(), label),
+            VerifyError::MissingReturn { block } =>
+                write!(fmt, "Block #{} reaches the end of the function without returning", block)
+        }
+    }
+}
+impl fmt::Debug for VerifyError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, fmt)
+    }
+}
+impl Error for VerifyError {
+    fn description(&self) -> &str {
+        match *self {
+            VerifyError::OperandType { .. } => "Operand of the wrong type",
+            VerifyError::StoreType { .. } => "Value stored into memory of the wrong type",
+            VerifyError::UndefinedLabel { .. } => "Branch to a label that is never placed",
+            VerifyError::MissingReturn { .. } => "Missing return"
+        }
+    }
+}
+
+/// Get the kind of `ty` once it has been normalized and had its tags removed
+fn normalized_kind(ty: &Ty) -> TypeKind {
+    unsafe {
+        mem::transmute(jit_type_get_kind(jit_type_normalize(ty.into())))
+    }
+}
+
+/// Check if a value of the type `ty` can be used where `kind` is expected
+fn accepts(kind: OperandKind, ty: &Ty) -> bool {
+    let ty_kind = normalized_kind(ty);
+    match kind {
+        OperandKind::Empty | OperandKind::Any => true,
+        OperandKind::Int | OperandKind::Long =>
+            ty_kind != TypeKind::Float32 && ty_kind != TypeKind::Float64 && ty_kind != TypeKind::NFloat,
+        OperandKind::Float32 => ty_kind == TypeKind::Float32,
+        OperandKind::Float64 => ty_kind == TypeKind::Float64,
+        OperandKind::NFloat => ty_kind == TypeKind::NFloat
+    }
+}
+
+/// Check if storing a value of the type `value` into memory that holds `slot`
+/// writes the right number of bytes in the right representation
+fn stores_into(value: &Ty, slot: &Ty) -> bool {
+    let (value_kind, slot_kind) = (normalized_kind(value), normalized_kind(slot));
+    value_kind == slot_kind || !value.is_float() && !slot.is_float()
+        && value.is_primitive() && slot.is_primitive()
+        && value.get_size() == slot.get_size()
+}
+
+/// Find the type of the primitive at `offset` bytes into `ty`
+fn type_at(ty: &Ty, offset: usize) -> Option<&Ty> {
+    if ty.is_struct() || ty.is_union() {
+        ty.fields()
+            .filter(|field| field.get_offset() <= offset && offset < field.get_offset() + field.get_type().get_size())
+            .filter_map(|field| type_at(field.get_type(), offset - field.get_offset()))
+            .next()
+    } else if offset == 0 {
+        Some(ty)
+    } else {
+        None
+    }
+}
+
+/// Check the type of the value a relative store writes against the type of
+/// the memory it writes into, if the pointer it writes through says
+fn check_store(func: &UncompiledFunction, insn: &Instruction, errors: &mut Vec<VerifyError>) {
+    let (ptr, value, offset) = match (insn.get_dest(), insn.get_value1(), insn.get_value2()) {
+        (Some(ptr), Some(value), Some(offset)) => (ptr, value, offset),
+        _ => return
+    };
+    let offset = unsafe {
+        if jit_value_is_constant(offset.into()) == 0 {
+            return
+        }
+        jit_value_get_nint_constant(offset.into())
+    };
+    let pointee = match ptr.get_type().get_ref() {
+        Some(pointee) if normalized_kind(pointee) != TypeKind::Void => pointee,
+        _ => return
+    };
+    if offset < 0 {
+        return
+    }
+    if let Some(slot) = type_at(pointee, offset as usize) {
+        if !stores_into(value.get_type(), slot) {
+            errors.push(VerifyError::StoreType {
+                insn: insn.dump(func),
+                expected: slot.to_owned(),
+                got: value.get_type().to_owned()
+            })
+        }
+    }
+}
+
+/// Check each instruction of `func`, then check that control can't fall off
+/// the end of it without returning a value
+pub fn verify(func: &UncompiledFunction) -> Result<(), Vec<VerifyError>> {
+    let mut errors = Vec::new();
+    let blocks: Vec<&Block> = func.blocks().collect();
+    for block in &blocks {
+        for insn in block.iter() {
+            if let Some(label) = insn.get_label() {
+                if insn.get_target(func).is_none() {
+                    errors.push(VerifyError::UndefinedLabel {
+                        insn: insn.dump(func),
+                        label: *label
+                    })
+                }
+            }
+            let opcode = match insn.get_opcode() {
+                Some(opcode) => opcode,
+                None => continue
+            };
+            let operands = opcode.operands();
+            let values = [
+                ("dest", operands.dest, insn.get_dest()),
+                ("value1", operands.value1, insn.get_value1()),
+                ("value2", operands.value2, insn.get_value2())
+            ];
+            for &(operand, kind, value) in &values {
+                if let Some(value) = value {
+                    if !accepts(kind, value.get_type()) {
+                        errors.push(VerifyError::OperandType {
+                            insn: insn.dump(func),
+                            operand: operand,
+                            expected: kind,
+                            got: value.get_type().to_owned()
+                        })
+                    }
+                }
+            }
+            match opcode {
+                Opcode::StoreRelativeByte | Opcode::StoreRelativeShort | Opcode::StoreRelativeInt
+                | Opcode::StoreRelativeLong | Opcode::StoreRelativeFloat32
+                | Opcode::StoreRelativeFloat64 | Opcode::StoreRelativeNFloat =>
+                    check_store(func, insn, &mut errors),
+                _ => ()
+            }
+        }
+    }
+    let returns_value = func.get_signature().get_return()
+        .map_or(false, |ty| normalized_kind(ty) != TypeKind::Void);
+    if returns_value && !blocks.is_empty() {
+        let mut reachable = vec![false; blocks.len()];
+        let mut pending = vec![0];
+        while let Some(index) = pending.pop() {
+            if reachable[index] {
+                continue
+            }
+            reachable[index] = true;
+            for next in blocks[index].successors() {
+                if let Some(next) = blocks.iter().position(|&block| block == next) {
+                    pending.push(next);
+                }
+            }
+        }
+        for (index, block) in blocks.iter().enumerate() {
+            if reachable[index] && !block.ends_in_dead() && block.next().is_none() {
+                errors.push(VerifyError::MissingReturn {
+                    block: index
+                })
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
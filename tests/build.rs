@@ -0,0 +1,43 @@
+extern crate jit;
+use jit::*;
+use std::sync::Arc;
+use std::thread;
+
+#[test]
+fn test_build_lock() {
+    let ctx = Context::<()>::new();
+    for n in 0..3 {
+        let result = ctx.build(|builder| {
+            let func = UncompiledFunction::new(builder, &get::<fn(i32) -> i32>());
+            func.insn_return(func.insn_add(&func[0], func.insn_of(n)));
+            let func = UncompiledFunction::compile(func).unwrap();
+            let add: extern fn(i32) -> i32 = func.as_func();
+            add(10)
+        });
+        assert_eq!(result, 10 + n);
+    }
+}
+
+#[test]
+fn test_sync_context() {
+    let ctx = match Context::<()>::new_sync() {
+        Some(ctx) => Arc::new(ctx),
+        None => return
+    };
+    let workers: Vec<_> = (0..8i32).map(|n| {
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let func = ctx.build(|builder| {
+                let func = UncompiledFunction::new(builder, &get::<fn(i32) -> i32>());
+                func.insn_return(func.insn_mul(&func[0], func.insn_of(n)));
+                let func = UncompiledFunction::compile(func).unwrap();
+                let mul: extern fn(i32) -> i32 = func.as_func();
+                mul
+            });
+            func(3)
+        })
+    }).collect();
+    for (n, worker) in workers.into_iter().enumerate() {
+        assert_eq!(worker.join().unwrap(), 3 * n as i32);
+    }
+}
@@ -0,0 +1,35 @@
+extern crate jit;
+use jit::*;
+
+#[test]
+fn test_cfg() {
+    let ctx = Context::<()>::new();
+    let func = UncompiledFunction::new(&ctx, &get::<fn(i32) -> i32>());
+    let x = &func[0];
+    let mut negative = Label::new(&func);
+    func.insn_branch_if(func.insn_lt(x, func.insn_of(0i32)), &mut negative);
+    func.insn_return(x);
+    func.insn_label(&mut negative);
+    let target = func.get_current().unwrap();
+    func.insn_return(func.insn_neg(x));
+    assert_eq!(*target.get_label().unwrap(), *negative);
+    let blocks: Vec<&Block> = func.blocks().collect();
+    assert!(blocks[0] == func.get_entry().unwrap());
+    assert!(blocks.contains(&target));
+    for pair in blocks.windows(2) {
+        assert!(pair[0].next() == Some(pair[1]));
+        assert!(pair[1].previous() == Some(pair[0]));
+    }
+    let branching = *blocks.iter()
+        .find(|block| block.iter().any(|insn| insn.is_branch()))
+        .unwrap();
+    let branch = branching.iter().find(|insn| insn.is_branch()).unwrap();
+    assert_eq!(*branch.get_label().unwrap(), *negative);
+    assert!(branch.get_target(&func) == Some(target));
+    assert!(branching.successors().contains(&target));
+    assert!(target.predecessors().contains(&branching));
+    let forwards: Vec<_> = branching.iter().map(|insn| insn as *const Instruction).collect();
+    let mut backwards: Vec<_> = branching.iter_rev().map(|insn| insn as *const Instruction).collect();
+    backwards.reverse();
+    assert_eq!(forwards, backwards);
+}
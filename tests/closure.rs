@@ -0,0 +1,61 @@
+extern crate jit;
+use jit::*;
+use jit::closure::{make_callback, CallbackError, PANIC_CODE};
+use jit::exception::{BuiltinException, JitException};
+use std::cell::Cell;
+use std::rc::Rc;
+
+#[test]
+fn test_callback() {
+    if !jit::supports_closures() {
+        return
+    }
+    let ctx = Context::<()>::new();
+    let scale = 3;
+    let callback = make_callback::<fn(i32, f64) -> i64, _, _>(&ctx, move |a: i32, b: f64| (a * scale) as i64 + b as i64).unwrap();
+    let func: extern fn(i32, f64) -> i64 = callback.as_fn();
+    assert_eq!(func(2, 4.0), 10);
+    assert_eq!(func(-1, 0.5), -3);
+}
+
+#[test]
+fn test_callback_state() {
+    if !jit::supports_closures() {
+        return
+    }
+    let ctx = Context::<()>::new();
+    let calls = Rc::new(Cell::new(0));
+    let counter = calls.clone();
+    let callback = make_callback::<fn(u8) -> (), _, _>(&ctx, move |x: u8| counter.set(counter.get() + x as usize)).unwrap();
+    let func: extern fn(u8) = callback.as_fn();
+    func(2);
+    func(5);
+    assert_eq!(calls.get(), 7);
+}
+
+#[test]
+fn test_callback_unsupported() {
+    if jit::supports_closures() {
+        return
+    }
+    let ctx = Context::<()>::new();
+    let err = make_callback::<fn() -> (), _, _>(&ctx, || ()).err().unwrap();
+    assert_eq!(err, CallbackError::Unsupported);
+}
+
+#[test]
+fn test_callback_panic() {
+    if !jit::supports_closures() {
+        return
+    }
+    let ctx = Context::<()>::new();
+    let callback = make_callback::<fn() -> i32, _, _>(&ctx, || panic!("callback panicked")).unwrap();
+    let func = UncompiledFunction::new(&ctx, &get::<fn() -> i32>());
+    let result = unsafe {
+        func.insn_call_native(Some("callback"), callback.as_ptr() as *mut (), &get::<extern fn() -> i32>(), &[], CallFlags::empty())
+    };
+    func.insn_return(result);
+    let func = UncompiledFunction::compile(func).unwrap();
+    let err = func.try_apply::<i32>(&[]).unwrap_err();
+    assert_eq!(err, JitException::Builtin(BuiltinException::Unknown(PANIC_CODE)));
+}
@@ -0,0 +1,65 @@
+extern crate jit;
+use jit::*;
+use jit::debugger::{self, Breakpoint};
+use std::cell::Cell;
+use std::rc::Rc;
+use std::thread;
+
+#[test]
+fn test_breakpoint_event() {
+    if !debugger::is_possible() {
+        return
+    }
+    let ctx = Context::<()>::new();
+    let func = UncompiledFunction::new(&ctx, &get::<fn(i32) -> i32>());
+    func.insn_mark_breakpoint(debugger::LINE, 42);
+    func.insn_return(func.insn_mul(&func[0], &func[0]));
+    let func = UncompiledFunction::compile(func).unwrap();
+    let debug = Debugger::new(&ctx);
+    debug.add_breakpoint(&Breakpoint {
+        function: Some(&**func),
+        data1: Some(debugger::LINE),
+        .. Default::default()
+    });
+    let square: extern fn(i32) -> i32 = func.as_func();
+    let worker = thread::spawn(move || square(7));
+    let mut stopped = None;
+    while let Some(event) = debug.wait_event(Some(5000)) {
+        if let DebuggerEvent::HardBreakpoint(stop) = event {
+            stopped = Some(stop);
+            break
+        }
+    }
+    let stop = stopped.expect("the breakpoint was never reported");
+    assert!(stop.function.unwrap() as *const Func == &**func as *const Func);
+    assert_eq!(stop.data1, debugger::LINE);
+    assert_eq!(stop.data2, 42);
+    assert!(!debug.is_running(stop.thread));
+    debug.run(stop.thread);
+    assert_eq!(worker.join().unwrap(), 49);
+}
+
+#[test]
+fn test_hook_cleared_on_drop() {
+    if !debugger::is_possible() {
+        return
+    }
+    let ctx = Context::<()>::new();
+    let func = UncompiledFunction::new(&ctx, &get::<fn() -> ()>());
+    func.insn_mark_breakpoint(debugger::LINE, 1);
+    func.insn_default_return();
+    let func = UncompiledFunction::compile(func).unwrap();
+    let hits = Rc::new(Cell::new(0));
+    {
+        let debug = Debugger::new(&ctx);
+        let counter = hits.clone();
+        debug.set_hook(move |_, _, _| counter.set(counter.get() + 1));
+        let call: extern fn() = func.as_func();
+        call();
+        assert_eq!(hits.get(), 1);
+    }
+    let call: extern fn() = func.as_func();
+    call();
+    assert_eq!(hits.get(), 1);
+    assert_eq!(Rc::strong_count(&hits), 1);
+}
@@ -0,0 +1,122 @@
+#[macro_use]
+extern crate jit_macros;
+#[macro_use]
+extern crate jit;
+use jit::*;
+
+#[repr(C)]
+#[derive(Compile, Copy, Clone, Debug, PartialEq)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32
+}
+
+#[repr(C)]
+#[derive(Compile, Copy, Clone, Debug, PartialEq)]
+pub struct Pair<T>(T, T);
+
+#[repr(C)]
+#[derive(Compile, Copy, Clone, Debug, PartialEq)]
+pub struct Call {
+    pub function: i32,
+    pub addressable: i32
+}
+
+#[repr(C)]
+#[derive(Compile, Copy, Clone, Debug, PartialEq)]
+pub struct Polygon {
+    pub corners: [Point; 3]
+}
+
+#[repr(C)]
+#[derive(Compile, Copy, Clone, Debug, PartialEq)]
+pub enum Shape {
+    Empty,
+    Circle(f64),
+    Rect { width: f64, height: f64 }
+}
+
+#[repr(C)]
+#[derive(Compile, Copy, Clone, Debug, PartialEq)]
+pub enum Colour {
+    Red = 1,
+    Green,
+    Blue = 8
+}
+
+#[test]
+fn test_derive_struct() {
+    let mut ctx = Context::<()>::new();
+    jit_func!(&mut ctx, func, fn(point: *mut Point) -> () {
+        func.insn_store_relative(point, 0, func.insn_of(Point { x: 3, y: 4 }));
+    }, {
+        let mut point = Point { x: 0, y: 0 };
+        func(&mut point);
+        assert_eq!(point, Point { x: 3, y: 4 });
+    });
+}
+
+#[test]
+fn test_derive_accessors() {
+    let mut ctx = Context::<()>::new();
+    jit_func!(&mut ctx, func, fn(point: *mut Point) -> i32 {
+        point.store_y(point.load_x());
+        func.insn_return(point.load_y());
+    }, {
+        let mut point = Point { x: 7, y: 0 };
+        assert_eq!(func(&mut point), 7);
+        assert_eq!(point, Point { x: 7, y: 7 });
+    });
+}
+
+#[test]
+fn test_derive_accessors_keep_val_methods() {
+    let mut ctx = Context::<()>::new();
+    jit_func!(&mut ctx, func, fn(call: *mut Call) -> i32 {
+        assert!(call.get_type().is_pointer());
+        call.store_addressable(call.load_function());
+        func.insn_return(call.load_addressable());
+    }, {
+        let mut call = Call { function: 5, addressable: 0 };
+        assert_eq!(func(&mut call), 5);
+        assert_eq!(call, Call { function: 5, addressable: 5 });
+    });
+}
+
+#[test]
+fn test_derive_generic_tuple() {
+    let mut ctx = Context::<()>::new();
+    jit_func!(&mut ctx, func, fn(pair: *mut Pair<f64>) -> () {
+        func.insn_store_relative(pair, 0, func.insn_of(Pair(1.5, 2.5)));
+    }, {
+        let mut pair = Pair(0.0, 0.0);
+        func(&mut pair);
+        assert_eq!(pair, Pair(1.5, 2.5));
+    });
+}
+
+#[test]
+fn test_derive_array() {
+    let ty = get::<Polygon>();
+    assert_eq!(ty.get_size(), std::mem::size_of::<Polygon>());
+    assert_eq!(ty.get_field("corners").unwrap().get_type().get_size(), 3 * std::mem::size_of::<Point>());
+}
+
+#[test]
+fn test_derive_enum() {
+    assert_eq!(get::<Shape>().get_size(), std::mem::size_of::<Shape>());
+    assert_eq!(get::<Colour>().get_size(), std::mem::size_of::<Colour>());
+    let mut ctx = Context::<()>::new();
+    jit_func!(&mut ctx, func, fn(shape: *mut Shape) -> () {
+        func.insn_store_relative(shape, 0, func.insn_of(Shape::Rect { width: 2.0, height: 3.0 }));
+    }, {
+        let mut shape = Shape::Empty;
+        func(&mut shape);
+        assert_eq!(shape, Shape::Rect { width: 2.0, height: 3.0 });
+    });
+    jit_func!(&mut ctx, func, fn() -> i32 {
+        func.insn_return(func.insn_of(Colour::Green));
+    }, {
+        assert_eq!(func(), Colour::Green as i32);
+    });
+}
@@ -0,0 +1,20 @@
+extern crate jit;
+use jit::*;
+
+#[test]
+fn test_dump() {
+    let ctx = Context::<()>::new();
+    let func = UncompiledFunction::new(&ctx, &get::<fn(i32) -> i32>());
+    func.insn_return(func.insn_add(&func[0], &func[0]));
+    let ir = func.to_string();
+    assert!(ir.starts_with("function"));
+    assert_eq!(ir, format!("{:?}", func));
+    let block = func.get_entry().unwrap();
+    for insn in block.iter() {
+        assert!(!insn.dump(&func).is_empty());
+    }
+    let func = UncompiledFunction::compile(func).unwrap();
+    assert!(!func.to_string().is_empty());
+    assert_eq!(get::<i32>().to_string(), "int");
+    assert_eq!(get::<*mut i32>().to_string(), "int *");
+}
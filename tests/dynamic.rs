@@ -0,0 +1,60 @@
+extern crate jit;
+use jit::*;
+use jit::exception::BuiltinException;
+
+#[test]
+fn test_apply_dyn() {
+    let ctx = Context::<()>::new();
+    let func = UncompiledFunction::new(&ctx, &get::<fn(i64, i64) -> i64>());
+    {
+        let (x, y) = (&func[0], &func[1]);
+        func.insn_return(func.insn_div(x, y));
+    }
+    let func = UncompiledFunction::compile(func).unwrap();
+    assert_eq!(func.apply_dyn(&[DynValue::Long(12), DynValue::Long(4)]), Ok(DynValue::Long(3)));
+    assert_eq!(func.apply_dyn(&[DynValue::Long(12)]), Err(ApplyError::ArgCount {
+        expected: 2,
+        got: 1
+    }));
+    match func.apply_dyn(&[DynValue::Long(12), DynValue::Float64(4.)]) {
+        Err(ApplyError::ArgType { index: 1, .. }) => (),
+        result => panic!("expected argument #1 to be rejected, got {:?}", result)
+    }
+    assert_eq!(func.apply_dyn(&[DynValue::Long(12), DynValue::Long(0)]),
+        Err(ApplyError::Exception(JitException::Builtin(BuiltinException::DivisionByZero))));
+    assert_eq!(func.apply_vararg(&[DynValue::Long(12), DynValue::Long(4)]), Err(ApplyError::NotVariadic));
+}
+
+#[test]
+fn test_apply_dyn_void() {
+    let ctx = Context::<()>::new();
+    let func = UncompiledFunction::new(&ctx, &get::<fn(*mut i32) -> ()>());
+    func.insn_store_relative(&func[0], 0, func.insn_of(7i32));
+    let func = UncompiledFunction::compile(func).unwrap();
+    let mut value = 0i32;
+    let ptr = &mut value as *mut i32 as *mut _;
+    assert_eq!(func.apply_dyn(&[DynValue::Pointer(ptr)]), Ok(DynValue::Void));
+    assert_eq!(value, 7);
+    assert!(!DynValue::Pointer(ptr).matches(&get::<isize>()));
+    assert!(!DynValue::NInt(0).matches(&get::<*mut i32>()));
+}
+
+#[test]
+fn test_struct_bytes() {
+    let ty = Type::new_struct(&[&get::<u8>(), &get::<f64>()]);
+    let mut value = DynValue::zeroed(&ty).unwrap();
+    assert_eq!(value.as_ptr() as usize % ty.get_alignment(), 0);
+    let offset = ty.fields().nth(1).unwrap().get_offset();
+    unsafe {
+        *((value.as_mut_ptr() as *mut u8).offset(offset as isize) as *mut f64) = 2.5;
+    }
+    let copy = value.clone();
+    assert_eq!(copy.as_ptr() as usize % ty.get_alignment(), 0);
+    assert_eq!(copy, value);
+    assert!(copy.matches(&ty));
+    match copy {
+        DynValue::Struct(_, ref bytes) => assert_eq!(bytes.len(), ty.get_size()),
+        _ => panic!("expected a struct")
+    }
+    assert_eq!(AlignedBytes::new(4, 0).len(), 4);
+}
@@ -0,0 +1,26 @@
+#[macro_use]
+extern crate jit;
+use jit::*;
+
+#[test]
+fn test_elem() {
+    let mut ctx = Context::<()>::new();
+    jit_func!(&mut ctx, func, fn(values: *mut i32, index: usize) -> i32 {
+        let value = func.insn_load_elem(values, index, &get::<i32>());
+        func.insn_store_elem(values, index, func.insn_mul(value, func.insn_of(2i32)));
+        func.insn_return(&values[index]);
+    }, {
+        let mut values = [1, 2, 3];
+        assert_eq!(func(values.as_mut_ptr(), 1), 4);
+        assert_eq!(func(values.as_mut_ptr(), 2), 6);
+        assert_eq!(values, [1, 4, 6]);
+    });
+}
+
+#[test]
+#[should_panic]
+fn test_elem_wrong_type() {
+    let ctx = Context::<()>::new();
+    let func = UncompiledFunction::new(&ctx, &get::<fn(*mut i32, usize) -> f64>());
+    func.insn_load_elem(&func[0], &func[1], &get::<f64>());
+}
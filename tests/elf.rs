@@ -0,0 +1,95 @@
+extern crate jit;
+use jit::*;
+use std::fs::File;
+use std::io::Write;
+use std::os::raw::c_void;
+use std::process::Command;
+
+extern fn triple(x: i32) -> i32 {
+    x * 3
+}
+
+#[test]
+fn test_open_errors() {
+    let error = ReadElf::new("does/not/exist.so").unwrap_err();
+    assert_eq!(error.get_code(), ReadElfErrorCode::CannotOpen);
+    assert_eq!(error.get_filename(), "does/not/exist.so");
+    assert_eq!(error.get_symbol(), None);
+    let error = ReadElf::new("Cargo.toml").unwrap_err();
+    assert_eq!(error.get_code(), ReadElfErrorCode::NotElf);
+    assert!(error.to_string().contains("Cargo.toml"));
+}
+
+#[test]
+fn test_register_and_resolve() {
+    let ctx = Context::<()>::new();
+    ReadElf::register_symbol(&ctx, "triple", triple as *mut c_void, false).unwrap();
+    ReadElf::register_symbol(&ctx, "triple_fallback", triple as *mut c_void, true).unwrap();
+    // With no binaries added there's nothing to relocate, so everything resolves
+    ReadElf::resolve_all(&ctx, false).unwrap();
+}
+
+/// Build a shared library that calls `triple` from the host program, or
+/// return `None` if there is no C compiler to build it with
+#[cfg(unix)]
+fn build_caller() -> Option<String> {
+    let dir = std::env::temp_dir();
+    let source = dir.join("jit-elf-caller.c");
+    let library = dir.join("jit-elf-caller.so");
+    File::create(&source).unwrap().write_all(b"
+        extern int triple(int x);
+        int call_triple(int x) {
+            return triple(x) + 1;
+        }
+    ").unwrap();
+    // LibJIT only reads SysV symbol hash tables
+    let status = Command::new("cc")
+        .args(&["-shared", "-fPIC", "-fno-plt", "-nostdlib", "-Wl,--hash-style=sysv", "-o"])
+        .arg(&library)
+        .arg(&source)
+        .status();
+    match status {
+        Ok(ref status) if status.success() => Some(library.to_str().unwrap().to_owned()),
+        _ => None
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn test_load_and_call() {
+    let path = match build_caller() {
+        Some(path) => path,
+        None => return
+    };
+    let ctx = Context::<()>::new();
+    ReadElf::register_symbol(&ctx, "triple", triple as *mut c_void, false).unwrap();
+    let reader = ReadElf::with_flags(&path, ReadElfFlags::FORCE).unwrap();
+    reader.add_to_context(&ctx);
+    ReadElf::resolve_all(&ctx, true).unwrap();
+    let call_triple = reader.get_function::<fn(i32) -> i32>("call_triple").unwrap();
+    assert_eq!(call_triple(4), 13);
+    assert_eq!(call_triple(-2), -5);
+}
+
+#[test]
+fn test_write_signatures() {
+    let ctx = Context::<()>::new();
+    let func = UncompiledFunction::new(&ctx, &get::<fn(i32) -> i32>());
+    func.insn_return(func.insn_add(&func[0], &func[0]));
+    let func = UncompiledFunction::compile(func).unwrap();
+    let path = std::env::temp_dir().join("jit-elf-test.so");
+    let path = path.to_str().unwrap();
+    let writer = WriteElf::new("libjit-elf-test.so");
+    assert!(writer.add_signature("double", func.get_signature()));
+    assert!(writer.add_needed("libm.so.6"));
+    assert!(writer.write(path));
+    let reader = ReadElf::new(path).unwrap();
+    assert!(reader.needed().any(|lib| lib == "libm.so.6"));
+    assert_eq!(reader.get_signature("double").unwrap().to_string(), get::<fn(i32) -> i32>().to_string());
+    assert!(reader.get_signature("triple").is_none());
+    let error = reader.get_function::<fn(f64) -> f64>("double").unwrap_err();
+    assert_eq!(error.get_code(), ReadElfErrorCode::WrongSignature);
+    assert_eq!(error.get_symbol(), Some("double"));
+    let error = reader.get_function::<fn(i32) -> i32>("triple").unwrap_err();
+    assert_eq!(error.get_code(), ReadElfErrorCode::NoSuchSymbol);
+}
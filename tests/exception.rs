@@ -0,0 +1,45 @@
+extern crate jit;
+use jit::*;
+use jit::exception::BuiltinException;
+
+#[test]
+fn test_try_apply_builtin() {
+    let ctx = Context::<()>::new();
+    let func = UncompiledFunction::new(&ctx, &get::<fn(i32, i32) -> i32>());
+    {
+        let (x, y) = (&func[0], &func[1]);
+        func.insn_return(func.insn_div(x, y));
+    }
+    let func = UncompiledFunction::compile(func).unwrap();
+    assert_eq!(func.try_apply::<i32>(&[&10i32, &2i32]), Ok(5));
+    assert_eq!(func.try_apply::<i32>(&[&10i32, &0i32]),
+        Err(JitException::Builtin(BuiltinException::DivisionByZero)));
+}
+
+#[test]
+fn test_try_apply_thrown() {
+    let ctx = Context::<()>::new();
+    let func = UncompiledFunction::new(&ctx, &get::<fn(i32) -> i32>());
+    {
+        let x = &func[0];
+        func.build_if(func.insn_eq(x, func.insn_of(0i32)), || {
+            func.insn_throw(func.insn_of(42usize as *const u8));
+        });
+        func.insn_return(x);
+    }
+    let func = UncompiledFunction::compile(func).unwrap();
+    assert_eq!(func.try_apply::<i32>(&[&3i32]), Ok(3));
+    assert_eq!(func.try_apply::<i32>(&[&0i32]),
+        Err(JitException::Thrown(42usize as *mut _)));
+}
+
+#[test]
+fn test_unknown_builtin() {
+    let unknown = BuiltinException::from_code(-42);
+    assert_eq!(unknown, BuiltinException::Unknown(-42));
+    assert_eq!(unknown.get_code(), -42);
+    assert_eq!(BuiltinException::from_code(-2), BuiltinException::DivisionByZero);
+    let exception = JitException::Builtin(unknown);
+    assert_eq!(JitException::from_ptr(exception.as_ptr()), exception);
+    assert_eq!(exception.as_ptr(), JitException::Builtin(BuiltinException::Unknown(-42)).as_ptr());
+}
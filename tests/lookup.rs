@@ -0,0 +1,19 @@
+extern crate jit;
+use jit::*;
+
+#[test]
+fn test_function_lookup() {
+    let ctx = Context::<()>::new();
+    let func = UncompiledFunction::new(&ctx, &get::<fn(i32) -> i32>());
+    func.insn_return(func.insn_mul(&func[0], &func[0]));
+    let func = UncompiledFunction::compile(func).unwrap();
+    let square: extern fn(i32) -> i32 = func.as_func();
+    let closure = square as *mut _;
+    assert_eq!(square(4), 16);
+    let found = ctx.function_from_closure(closure).unwrap();
+    assert_eq!(found.get_signature(), func.get_signature());
+    assert!(ctx.function_from_pc(closure).is_some());
+    let other = Context::<()>::new();
+    assert!(other.function_from_closure(closure).is_none());
+    assert!(other.function_from_pc(closure).is_none());
+}
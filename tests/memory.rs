@@ -0,0 +1,36 @@
+extern crate jit;
+use jit::*;
+
+#[test]
+fn test_code_limit() {
+    let limit = CodeLimit::new(1 << 20);
+    let stats = limit.stats();
+    let ctx = Context::<()>::with_memory_manager(limit);
+    let double = UncompiledFunction::new(&ctx, &get::<fn(i32) -> i32>());
+    double.insn_return(double.insn_add(&double[0], &double[0]));
+    let double = UncompiledFunction::compile(double).unwrap();
+    let square = UncompiledFunction::new(&ctx, &get::<fn(i32) -> i32>());
+    square.insn_return(square.insn_mul(&square[0], &square[0]));
+    let square = UncompiledFunction::compile(square).unwrap();
+    assert_eq!(stats.functions(), 2);
+    let sizes = stats.function_code_size(&double).unwrap() + stats.function_code_size(&square).unwrap();
+    assert_eq!(stats.code_size(), sizes);
+    assert_eq!(stats.peak_code_size(), sizes);
+    let double_fn: extern fn(i32) -> i32 = double.as_func();
+    assert_eq!(double_fn(21), 42);
+}
+
+#[test]
+fn test_code_limit_exceeded() {
+    let limit = CodeLimit::new(1);
+    let stats = limit.stats();
+    let ctx = Context::<()>::with_memory_manager(limit);
+    let func = UncompiledFunction::new(&ctx, &get::<fn(i32) -> i32>());
+    func.insn_return(func.insn_add(&func[0], &func[0]));
+    match UncompiledFunction::compile(func) {
+        Err(CompileError::Failed) => (),
+        _ => panic!("expected the code limit to stop compilation")
+    }
+    assert!(stats.rejected() > 0);
+    assert_eq!(stats.code_size(), 0);
+}
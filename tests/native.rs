@@ -0,0 +1,24 @@
+extern crate jit;
+use jit::*;
+
+extern fn scale(x: f64, factor: i32) -> f64 {
+    x * factor as f64
+}
+
+extern fn sum(x: i64, y: i64, z: i64) -> i64 {
+    x + y + z
+}
+
+#[test]
+fn test_call_native() {
+    let sig = Type::new_signature(Abi::CDecl, &get::<f64>(), &mut [&get::<f64>(), &get::<i32>()]);
+    let result = unsafe { call_native(scale as *mut _, &sig, &[DynValue::Float64(1.5), DynValue::Int(4)]) };
+    assert_eq!(result, Ok(DynValue::Float64(6.0)));
+    let sig = get::<fn(i64, i64, i64) -> i64>();
+    let args = [DynValue::Long(1), DynValue::Long(20), DynValue::Long(300)];
+    assert_eq!(unsafe { call_native(sum as *mut _, &sig, &args) }, Ok(DynValue::Long(321)));
+    assert_eq!(unsafe { call_native(sum as *mut _, &sig, &args[..2]) }, Err(ApplyError::ArgCount {
+        expected: 3,
+        got: 2
+    }));
+}
@@ -0,0 +1,39 @@
+extern crate jit;
+use jit::*;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[test]
+fn test_on_demand() {
+    let ctx = Context::<()>::new();
+    let builds = Arc::new(AtomicUsize::new(0));
+    let counter = builds.clone();
+    let func = CompiledFunction::new_on_demand(&ctx, &get::<fn(i32) -> i32>(), move |func| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        let x = &func[0];
+        func.insn_return(func.insn_add(x, func.insn_of(1i32)));
+    });
+    assert!(!func.is_compiled());
+    assert_eq!(builds.load(Ordering::SeqCst), 0);
+    let add_one: extern fn(i32) -> i32 = func.as_func();
+    assert_eq!(add_one(1), 2);
+    assert_eq!(add_one(41), 42);
+    assert!(func.is_compiled());
+    assert_eq!(builds.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_on_demand_driver() {
+    let ctx = Context::<()>::new();
+    let drives = Arc::new(AtomicUsize::new(0));
+    let counter = drives.clone();
+    ctx.set_on_demand_driver(move |func| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        func.compile_on_demand()
+    });
+    let func = CompiledFunction::new_on_demand(&ctx, &get::<fn(i32) -> i32>(), |func| {
+        func.insn_return(func.insn_neg(&func[0]));
+    });
+    assert_eq!(func.apply::<i32>(&[&5i32]), -5);
+    assert_eq!(drives.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,49 @@
+extern crate jit;
+use jit::*;
+
+#[test]
+fn test_opcodes() {
+    for op in Opcode::all() {
+        let raw = op.to_raw().expect(op.name());
+        assert_eq!(Opcode::from_raw(raw), Some(op));
+    }
+    assert_eq!(Opcode::from_raw(0), Some(Opcode::Nop));
+    assert_eq!(Opcode::from_raw(-1), None);
+    let add = Opcode::Iadd.operands();
+    assert_eq!(add.dest, OperandKind::Int);
+    assert_eq!(add.value1, OperandKind::Int);
+    assert_eq!(add.value2, OperandKind::Int);
+    assert!(!add.label && !add.signature && !add.native);
+    assert!(Opcode::BrItrue.is_branch());
+    assert!(Opcode::BrItrue.operands().label);
+    assert!(Opcode::CallExternal.operands().native);
+    assert!(Opcode::CallIndirect.operands().signature);
+    assert!(Opcode::ReturnInt.is_terminator());
+}
+
+#[test]
+fn test_terminators() {
+    let returns: Vec<Opcode> = Opcode::all().filter(|op| op.is_return()).collect();
+    assert_eq!(returns, vec![Opcode::Return, Opcode::ReturnInt, Opcode::ReturnLong,
+        Opcode::ReturnFloat32, Opcode::ReturnFloat64, Opcode::ReturnNFloat, Opcode::ReturnSmallStruct]);
+    for op in &[Opcode::Br, Opcode::Throw, Opcode::Rethrow, Opcode::CallTail, Opcode::CallIndirectTail,
+                Opcode::CallVtablePtrTail, Opcode::CallExternalTail] {
+        assert!(op.is_terminator(), "{:?} should be a terminator", op);
+    }
+    for op in &[Opcode::BrItrue, Opcode::Call, Opcode::CallFinally, Opcode::ReturnReg, Opcode::JumpTable] {
+        assert!(!op.is_terminator(), "{:?} shouldn't be a terminator", op);
+    }
+}
+
+#[test]
+fn test_insn_opcodes() {
+    let ctx = Context::<()>::new();
+    let func = UncompiledFunction::new(&ctx, &get::<fn(i32, i32) -> i32>());
+    func.insn_return(func.insn_add(&func[0], &func[1]));
+    let opcodes: Vec<Opcode> = func.blocks()
+        .flat_map(|block| block.iter())
+        .filter_map(|insn| insn.get_opcode())
+        .collect();
+    assert!(opcodes.contains(&Opcode::Iadd));
+    assert!(opcodes.contains(&Opcode::ReturnInt));
+}
@@ -0,0 +1,62 @@
+extern crate jit;
+use jit::*;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake};
+use std::thread::{self, Thread};
+
+struct Unpark(Thread);
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark()
+    }
+}
+
+fn block_on<F: Future>(mut future: F) -> F::Output {
+    let waker = Arc::new(Unpark(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = unsafe { Pin::new_unchecked(&mut future) };
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park()
+        }
+    }
+}
+
+#[test]
+fn test_compile_queue() {
+    let queue = match CompileQueue::new(4) {
+        Some(queue) => queue,
+        None => return
+    };
+    let futures: Vec<_> = (0..16i32).map(|n| {
+        queue.compile::<fn(i32, i32) -> i32, _>(move |func| {
+            let sum = func.insn_add(&func[0], &func[1]);
+            func.insn_return(func.insn_add(sum, func.insn_of(n)));
+        })
+    }).collect();
+    let funcs: Vec<_> = futures.into_iter().map(|future| block_on(future).unwrap()).collect();
+    let handles: Vec<_> = funcs.into_iter().enumerate().map(|(n, func)| {
+        thread::spawn(move || assert_eq!(func.as_fn()(1, 2), 3 + n as i32))
+    }).collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+#[test]
+fn test_compile_queue_panic() {
+    let queue = match CompileQueue::new(1) {
+        Some(queue) => queue,
+        None => return
+    };
+    let failed = queue.compile::<fn() -> i32, _>(|_| panic!("builder failed"));
+    match block_on(failed) {
+        Err(QueueError::Panicked) => (),
+        _ => panic!("expected the builder to panic")
+    }
+    let answer = queue.compile::<fn() -> i32, _>(|func| func.insn_return(func.insn_of(42i32)));
+    assert_eq!(block_on(answer).unwrap().as_fn()(), 42);
+}
@@ -0,0 +1,57 @@
+extern crate jit;
+use jit::*;
+use jit::exception::BuiltinException;
+
+static VALUES: [i32; 3] = [3, 5, 7];
+
+#[test]
+fn test_slice_len() {
+    let ctx = Context::<()>::new();
+    let func = UncompiledFunction::new(&ctx, &get::<fn(&[i32]) -> usize>());
+    func.insn_return(func.insn_slice_len(&func[0]));
+    let func = UncompiledFunction::compile(func).unwrap();
+    let len: extern fn(&[i32]) -> usize = func.as_func();
+    assert_eq!(len(&VALUES), 3);
+    assert_eq!(len(&VALUES[1..]), 2);
+}
+
+#[test]
+fn test_slice_checked() {
+    let ctx = Context::<()>::new();
+    let func = UncompiledFunction::new(&ctx, &get::<fn(&[i32], usize) -> i32>());
+    func.insn_return(func.insn_load_elem_checked(&func[0], &func[1]));
+    let func = UncompiledFunction::compile(func).unwrap();
+    let values: &'static [i32] = &VALUES;
+    assert_eq!(func.try_apply::<i32>(&[&values, &2usize]), Ok(7));
+    assert_eq!(func.try_apply::<i32>(&[&values, &3usize]),
+        Err(JitException::Builtin(BuiltinException::OutOfBounds)));
+}
+
+#[test]
+fn test_vec() {
+    let ctx = Context::<()>::new();
+    let func = UncompiledFunction::new(&ctx, &get::<fn() -> i32>());
+    {
+        let values = func.insn_of_vec(vec![1i32, 2, 4, 8]);
+        let a = func.insn_load_elem_checked(values, func.insn_of(1usize));
+        let b = func.insn_load_elem_checked(values, func.insn_of(3usize));
+        func.insn_return(func.insn_add(a, b));
+    }
+    let func = UncompiledFunction::compile(func).unwrap();
+    assert_eq!(func.apply::<i32>(&[]), 10);
+}
+
+#[test]
+fn test_array() {
+    let ctx = Context::<()>::new();
+    let func = UncompiledFunction::new(&ctx, &get::<fn(*const [f64; 3]) -> f64>());
+    {
+        let array = &func[0];
+        func.insn_return(func.insn_mul(&array[0], &array[2]));
+    }
+    let func = UncompiledFunction::compile(func).unwrap();
+    let array = [2.0, 3.0, 4.0];
+    let mul: extern fn(*const [f64; 3]) -> f64 = func.as_func();
+    assert_eq!(mul(&array), 8.0);
+    assert_eq!(get::<[f64; 3]>().get_size(), 24);
+}
@@ -0,0 +1,67 @@
+extern crate jit;
+use jit::*;
+
+#[test]
+fn test_tiered() {
+    let ctx = Context::<()>::new();
+    let func = TieredFunction::new(&ctx, &get::<fn(i32, i32) -> i32>(), 3, |func| {
+        let (x, y) = (&func[0], &func[1]);
+        func.insn_return(func.insn_sub(x, y));
+    }).unwrap();
+    assert!(func.is_recompilable());
+    let sub: extern fn((i32, i32)) -> i32 = func.as_func();
+    let sub: extern fn(i32, i32) -> i32 = unsafe { std::mem::transmute(sub) };
+    assert_eq!(sub(5, 3), 2);
+    assert_eq!(sub(3, 5), -2);
+    assert!(!func.is_hot());
+    assert!(!func.is_promoted());
+    assert_eq!(sub(0, 0), 0);
+    assert_eq!(func.get_calls(), 3);
+    assert!(func.is_hot());
+    assert!(func.is_promoted());
+    assert!(!func.promote_if_hot());
+    assert_eq!(func.get_optimization_level(), UncompiledFunction::get_max_optimization_level());
+    assert_eq!(sub(10, 4), 6);
+    assert_eq!(func.get_calls(), 3);
+}
+
+#[test]
+fn test_tiered_threads() {
+    let ctx = match Context::<()>::new_sync() {
+        Some(ctx) => ctx,
+        None => return
+    };
+    let func = TieredFunction::new(&ctx, &get::<fn(i32) -> i32>(), 100, |func| {
+        func.insn_return(func.insn_add(&func[0], &func[0]));
+    }).unwrap();
+    let double: extern fn(i32) -> i32 = func.as_func();
+    let workers: Vec<_> = (0..4).map(|_| std::thread::spawn(move || {
+        for i in 0..50 {
+            assert_eq!(double(i), i * 2);
+        }
+    })).collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    assert!(func.get_calls() >= 100);
+    assert!(func.is_promoted());
+}
+
+#[test]
+fn test_tiered_while_building() {
+    let ctx = Context::<()>::new();
+    let func = TieredFunction::new(&ctx, &get::<fn(i32) -> i32>(), 2, |func| {
+        func.insn_return(func.insn_neg(&func[0]));
+    }).unwrap();
+    let negate: extern fn(i32) -> i32 = func.as_func();
+    ctx.build(|_| {
+        assert!(ctx.is_building());
+        assert_eq!(negate(1), -1);
+        assert_eq!(negate(2), -2);
+    });
+    assert!(!ctx.is_building());
+    assert!(func.is_hot());
+    assert!(!func.is_promoted());
+    assert_eq!(negate(3), -3);
+    assert!(func.is_promoted());
+}
@@ -0,0 +1,91 @@
+extern crate cbox;
+extern crate jit;
+use cbox::CSemiBox;
+use jit::*;
+use std::cell::{Cell, RefCell};
+use std::ptr;
+
+/// A JIT function a callback found on the stack, and the location of its frame
+type Found = Option<(usize, Option<SourceLocation>)>;
+
+thread_local!(
+    static FRAMES: Cell<usize> = Cell::new(0);
+    static CONTEXT: Cell<*const Context<()>> = Cell::new(ptr::null());
+    static FOUND: RefCell<Found> = RefCell::new(None)
+);
+
+/// Find the first frame that is in a JIT function
+fn find<'a, I>(frames: I) -> Found where I: Iterator<Item = StackFrame<'a>> {
+    frames
+        .filter_map(|frame| frame.function.map(|func| (func as *const Func as usize, frame.get_location().cloned())))
+        .next()
+}
+
+extern fn record(x: i32) -> i32 {
+    let trace = StackTrace::capture().unwrap();
+    let ctx = unsafe { &*CONTEXT.with(Cell::get) };
+    FRAMES.with(|frames| frames.set(trace.len()));
+    FOUND.with(|found| *found.borrow_mut() = find(trace.frames(ctx)));
+    x
+}
+
+extern fn unwind(x: i32) -> i32 {
+    let ctx = unsafe { &*CONTEXT.with(Cell::get) };
+    FOUND.with(|found| *found.borrow_mut() = find(Unwinder::new(ctx).unwrap()));
+    x
+}
+
+/// Make a function that marks a location then calls `callback`
+fn make_caller<'a>(ctx: &'a Context<()>, callback: extern fn(i32) -> i32) -> CSemiBox<'a, CompiledFunction> {
+    let func = UncompiledFunction::new(ctx, &get::<fn(i32) -> i32>());
+    {
+        let x = &func[0];
+        func.insn_mark_location(7, SourceLocation::new("trace.script", 3, 9));
+        let v = func.insn_call_rust(Some("callback"), callback, &[x], CallFlags::empty());
+        func.insn_return(v);
+    }
+    UncompiledFunction::compile(func).unwrap()
+}
+
+#[test]
+fn test_stack_trace() {
+    let ctx = Context::<()>::new();
+    let func = make_caller(&ctx, record);
+    CONTEXT.with(|context| context.set(&ctx));
+    let call: extern fn(i32) -> i32 = func.as_func();
+    assert_eq!(call(3), 3);
+    assert!(FRAMES.with(Cell::get) > 0);
+    let (found, location) = FOUND.with(|found| found.borrow_mut().take()).expect("no frame was in a JIT function");
+    assert_eq!(found, &*func as *const CompiledFunction as usize);
+    assert_eq!(location, Some(SourceLocation::new("trace.script", 3, 9)));
+}
+
+#[test]
+fn test_unwinder() {
+    let ctx = Context::<()>::new();
+    let func = make_caller(&ctx, unwind);
+    CONTEXT.with(|context| context.set(&ctx));
+    let call: extern fn(i32) -> i32 = func.as_func();
+    assert_eq!(call(5), 5);
+    let (found, location) = FOUND.with(|found| found.borrow_mut().take()).expect("the unwinder didn't walk the JIT frame");
+    assert_eq!(found, &*func as *const CompiledFunction as usize);
+    assert_eq!(location, Some(SourceLocation::new("trace.script", 3, 9)));
+}
+
+#[test]
+fn test_source_map() {
+    let ctx = Context::<()>::new();
+    let func = UncompiledFunction::new(&ctx, &get::<fn(i32) -> i32>());
+    {
+        let x = &func[0];
+        func.insn_mark_location(1, SourceLocation::new("test.script", 1, 1));
+        let y = func.insn_mul(x, x);
+        func.insn_mark_location(2, SourceLocation::new("test.script", 2, 5));
+        func.insn_return(y);
+    }
+    let func = UncompiledFunction::compile(func).unwrap();
+    assert_eq!(func.get_location(1), Some(&SourceLocation::new("test.script", 1, 1)));
+    assert_eq!(func.get_location(2).unwrap().column, 5);
+    assert_eq!(func.get_location(3), None);
+    assert_eq!(func.get_source_map().unwrap().len(), 2);
+}
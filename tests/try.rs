@@ -0,0 +1,120 @@
+extern crate jit;
+use jit::*;
+
+#[test]
+fn test_try() {
+    let mut ctx = Context::<()>::new();
+    jit_func!(&mut ctx, func, fn(x: i32) -> i32 {
+        let result = Val::new(func, &get::<i32>());
+        func.insn_store(result, x);
+        func.build_try(|| {
+            func.build_if(func.insn_eq(x, func.insn_of(0i32)), || {
+                func.insn_throw(func.insn_of(1usize as *const u8));
+            });
+        }, |_| {
+            func.insn_store(result, func.insn_of(-1i32));
+        }, || {
+            func.insn_store(result, func.insn_mul(result, func.insn_of(2i32)));
+        });
+        func.insn_return(result);
+    }, {
+        assert_eq!(func(4), 8);
+        assert_eq!(func(1), 2);
+        assert_eq!(func(0), -2);
+    });
+}
+
+#[test]
+fn test_try_twice() {
+    let mut ctx = Context::<()>::new();
+    jit_func!(&mut ctx, func, fn(x: i32) -> i32 {
+        let result = Val::new(func, &get::<i32>());
+        func.insn_store(result, func.insn_of(0i32));
+        func.build_try(|| {
+            func.build_if(func.insn_eq(x, func.insn_of(1i32)), || {
+                func.insn_throw(func.insn_of(1usize as *const u8));
+            });
+        }, |_| {
+            func.insn_store(result, func.insn_of(10i32));
+        }, || ());
+        func.build_try(|| {
+            func.build_if(func.insn_eq(x, func.insn_of(2i32)), || {
+                func.insn_throw(func.insn_of(1usize as *const u8));
+            });
+        }, |_| {
+            func.insn_store(result, func.insn_add(result, func.insn_of(20i32)));
+        }, || ());
+        func.insn_return(result);
+    }, {
+        assert_eq!(func(0), 0);
+        assert_eq!(func(1), 10);
+        assert_eq!(func(2), 20);
+    });
+}
+
+#[test]
+fn test_try_nested() {
+    let mut ctx = Context::<()>::new();
+    jit_func!(&mut ctx, func, fn(x: i32) -> i32 {
+        let result = Val::new(func, &get::<i32>());
+        func.insn_store(result, func.insn_of(0i32));
+        func.build_try(|| {
+            func.build_try(|| {
+                func.build_if(func.insn_eq(x, func.insn_of(1i32)), || {
+                    func.insn_throw(func.insn_of(1usize as *const u8));
+                });
+            }, |_| {
+                func.insn_store(result, func.insn_of(1i32));
+                // Rethrow, which the outer handler should catch
+                func.build_if(func.insn_eq(x, func.insn_of(1i32)), || {
+                    func.insn_throw(func.insn_of(2usize as *const u8));
+                });
+            }, || {
+                func.insn_store(result, func.insn_add(result, func.insn_of(100i32)));
+            });
+        }, |exception| {
+            func.insn_store(result, func.insn_add(result, func.insn_convert(exception, &get::<i32>(), false)));
+        }, || ());
+        func.insn_return(result);
+    }, {
+        // The inner finally still runs when the inner handler throws
+        assert_eq!(func(0), 100);
+        assert_eq!(func(1), 103);
+    });
+}
+
+#[test]
+fn test_try_in_handler() {
+    let mut ctx = Context::<()>::new();
+    jit_func!(&mut ctx, func, fn(x: i32) -> i32 {
+        let result = Val::new(func, &get::<i32>());
+        func.insn_store(result, func.insn_of(0i32));
+        func.build_try(|| {
+            func.insn_throw(func.insn_of(5usize as *const u8));
+        }, |exception| {
+            func.build_try(|| {
+                func.build_if(func.insn_eq(x, func.insn_of(1i32)), || {
+                    func.insn_throw(func.insn_of(7usize as *const u8));
+                });
+            }, |inner| {
+                func.insn_store(result, func.insn_convert(inner, &get::<i32>(), false));
+            }, || ());
+            // This handler's exception is still the one it was given
+            let outer = func.insn_convert(exception, &get::<i32>(), false);
+            func.insn_store(result, func.insn_add(result, func.insn_mul(outer, func.insn_of(10i32))));
+        }, || ());
+        func.insn_return(result);
+    }, {
+        assert_eq!(func(0), 50);
+        assert_eq!(func(1), 57);
+    });
+}
+
+#[test]
+#[should_panic]
+fn test_try_then_start_catcher() {
+    let ctx = Context::<()>::new();
+    let func = UncompiledFunction::new(&ctx, &get::<fn()>());
+    func.build_try(|| (), |_| (), || ());
+    func.insn_start_catcher();
+}
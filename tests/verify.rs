@@ -0,0 +1,57 @@
+extern crate jit;
+use jit::*;
+use std::mem;
+
+#[test]
+fn test_verify_ok() {
+    let ctx = Context::<()>::new();
+    let func = UncompiledFunction::new(&ctx, &get::<fn(*mut i32, i32) -> i32>());
+    func.insn_store_relative(&func[0], 0, &func[1]);
+    func.insn_return(func.insn_mul(&func[1], &func[1]));
+    assert_eq!(func.verify(), Ok(()));
+    let func = UncompiledFunction::compile_checked(func).unwrap();
+    let mut slot = 0;
+    let square: extern fn((*mut i32, i32)) -> i32 = func.as_func();
+    let square: extern fn(*mut i32, i32) -> i32 = unsafe { mem::transmute(square) };
+    assert_eq!(square(&mut slot, 3), 9);
+    assert_eq!(slot, 3);
+}
+
+#[test]
+fn test_verify_store() {
+    let ctx = Context::<()>::new();
+    let func = UncompiledFunction::new(&ctx, &get::<fn(*mut i32)>());
+    func.insn_store_relative(&func[0], 0, func.insn_of(1.5f64));
+    func.insn_default_return();
+    match func.verify() {
+        Err(ref errors) if errors.len() == 1 => match errors[0] {
+            VerifyError::StoreType { ref expected, ref got, .. } => {
+                assert_eq!(expected.get_kind(), TypeKind::Int);
+                assert_eq!(got.get_kind(), TypeKind::Float64);
+            },
+            ref error => panic!("unexpected error: {}", error)
+        },
+        result => panic!("unexpected result: {:?}", result)
+    }
+}
+
+#[test]
+fn test_verify_label_and_return() {
+    let ctx = Context::<()>::new();
+    let func = UncompiledFunction::new(&ctx, &get::<fn(i32) -> i32>());
+    let mut nowhere = Label::new(&func);
+    func.insn_branch_if(&func[0], &mut nowhere);
+    let errors = func.verify().unwrap_err();
+    assert!(errors.iter().any(|error| match *error {
+        VerifyError::UndefinedLabel { label, .. } => label == *nowhere,
+        _ => false
+    }));
+    assert!(errors.iter().any(|error| match *error {
+        VerifyError::MissingReturn { .. } => true,
+        _ => false
+    }));
+    match UncompiledFunction::compile_checked(func) {
+        Err(CompileError::Invalid(ref invalid)) => assert_eq!(invalid, &errors),
+        _ => panic!("expected the function to fail verification")
+    }
+}
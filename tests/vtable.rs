@@ -0,0 +1,28 @@
+extern crate jit;
+use jit::*;
+
+#[test]
+fn test_vtable() {
+    let ctx = Context::<()>::new();
+    let sig = get::<fn(i32) -> i32>();
+    let double = UncompiledFunction::new(&ctx, &sig);
+    double.insn_return(double.insn_add(&double[0], &double[0]));
+    let double = UncompiledFunction::compile(double).unwrap();
+    let negate = UncompiledFunction::new(&ctx, &sig);
+    negate.insn_return(negate.insn_neg(&negate[0]));
+    let negate = UncompiledFunction::compile(negate).unwrap();
+    let vtable = [double.to_vtable_pointer() as *const u8, negate.to_vtable_pointer() as *const u8];
+    assert_eq!(ctx.function_from_vtable_pointer(vtable[1] as *mut _).map(|func| func.to_vtable_pointer()),
+        Some(negate.to_vtable_pointer()));
+    let dispatch = UncompiledFunction::new(&ctx, &get::<fn(*const *const u8, usize, i32) -> i32>());
+    {
+        let (methods, index, x) = (&dispatch[0], &dispatch[1], &dispatch[2]);
+        let method = &methods[index];
+        dispatch.insn_return(dispatch.insn_call_indirect_vtable(method, &sig, &[x], CallFlags::empty()));
+    }
+    let dispatch = UncompiledFunction::compile(dispatch).unwrap();
+    let dispatch: extern fn((*const *const u8, usize, i32)) -> i32 = dispatch.as_func();
+    let dispatch: extern fn(*const *const u8, usize, i32) -> i32 = unsafe { std::mem::transmute(dispatch) };
+    assert_eq!(dispatch(vtable.as_ptr(), 0, 21), 42);
+    assert_eq!(dispatch(vtable.as_ptr(), 1, 21), -21);
+}